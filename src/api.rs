@@ -0,0 +1,597 @@
+use anyhow::{anyhow, Context, Result};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use colored::*;
+
+use graphql_client::{GraphQLQuery, Response};
+
+use graphql_parser::query::{Definition, OperationDefinition};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::config;
+use crate::fixture::{self, Fixtures};
+use crate::retry;
+use crate::trace::{self, Tracer};
+
+/// Kitemaker's GraphQL endpoint
+pub const ENDPOINT: &str = "https://toil.kitemaker.co/developers/graphql";
+
+/// Sent as the User-Agent header unless configured otherwise
+const USER_AGENT: &str = concat!("km/", env!("CARGO_PKG_VERSION"));
+
+/// Seconds to wait for a response unless configured otherwise
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
+/// Times a failed query is sent again unless configured otherwise
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// The API returns at most this many work items per page
+pub const MAX_PAGE_SIZE: i64 = 50;
+
+/// The `Date` scalar. The API has been seen returning both epoch milliseconds and
+/// ISO 8601 strings, so accept either.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date(pub DateTime<Utc>);
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Millis(i64),
+            Text(String),
+        }
+
+        let parsed = match Raw::deserialize(deserializer)? {
+            Raw::Millis(ms) => Utc.timestamp_millis_opt(ms).single(),
+            Raw::Text(s) => match s.parse::<i64>() {
+                Ok(ms) => Utc.timestamp_millis_opt(ms).single(),
+                Err(_) => DateTime::parse_from_rfc3339(&s)
+                    .ok()
+                    .map(|d| d.with_timezone(&Utc)),
+            },
+        };
+        parsed
+            .map(Date)
+            .ok_or_else(|| serde::de::Error::custom("invalid date"))
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_rfc3339())
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.format("%Y-%m-%d"))
+    }
+}
+
+/// The `JSON` scalar, e.g. the type-specific details of an activity
+#[allow(clippy::upper_case_acronyms)]
+pub type JSON = serde_json::Value;
+
+// GraphQL queries
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq,Serialize"
+)]
+pub struct SpaceQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    variables_derives = "Clone",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct ItemsQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct ItemQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct CreateWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct SearchItemsQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct SearchItemsWithCommentsQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct ItemByNumberQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct MoveWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct AddMembersToWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct RemoveMembersFromWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct AddLabelsToWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct RemoveLabelsFromWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct CommentOnWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct RankWorkItem;
+
+/// Only the fields that are set are sent, so the others are left as they are
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq",
+    skip_serializing_none
+)]
+pub struct EditWorkItem;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct CodeReviewQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/kitemaker.graphql",
+    query_path = "src/queries.graphql",
+    response_derives = "Debug,PartialEq"
+)]
+pub struct EditCodeReviewRequest;
+
+pub type WorkItem = items_query::ItemsQueryWorkItemsWorkItems;
+
+impl FromStr for items_query::StatusType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().replace('-', "_").as_str() {
+            "BACKLOG" => Ok(items_query::StatusType::BACKLOG),
+            "TODO" => Ok(items_query::StatusType::TODO),
+            "IN_PROGRESS" => Ok(items_query::StatusType::IN_PROGRESS),
+            "DONE" => Ok(items_query::StatusType::DONE),
+            "ARCHIVED" => Ok(items_query::StatusType::ARCHIVED),
+            _ => Err(format!("Unknown status type {:}", s)),
+        }
+    }
+}
+
+/// The errors the API answered a query with, as opposed to not getting an answer at all
+#[derive(Debug)]
+pub struct QueryError {
+    pub operation: String,
+    pub messages: Vec<String>,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} failed: {}", self.operation, self.messages.join(", "))
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Every query module generates its own `StatusType`, convert them for `ItemsQuery` filters
+macro_rules! into_items_query_status_type {
+    ($($module:ident),*) => {
+        $(
+            impl From<&$module::StatusType> for items_query::StatusType {
+                fn from(status_type: &$module::StatusType) -> Self {
+                    match status_type {
+                        $module::StatusType::BACKLOG => items_query::StatusType::BACKLOG,
+                        $module::StatusType::TODO => items_query::StatusType::TODO,
+                        $module::StatusType::IN_PROGRESS => items_query::StatusType::IN_PROGRESS,
+                        $module::StatusType::DONE => items_query::StatusType::DONE,
+                        $module::StatusType::ARCHIVED => items_query::StatusType::ARCHIVED,
+                        $module::StatusType::Other(s) => items_query::StatusType::Other(s.clone()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+into_items_query_status_type!(space_query, item_by_number_query);
+
+/// How to reach the API and what to do when a request fails
+pub struct Options {
+    /// GraphQL endpoint, Kitemaker's if not set
+    pub endpoint: Option<String>,
+    /// How long a single request may take
+    pub timeout: Duration,
+    /// Times a failed request is sent again
+    pub retries: u32,
+    /// Also retry mutations, which may then be applied twice
+    pub retry_mutations: bool,
+    /// Proxy URL for all requests
+    pub proxy: Option<String>,
+    /// Comma-separated hosts to reach without `proxy`
+    pub no_proxy: Option<String>,
+    /// PEM bundles of extra certificate authorities to trust
+    pub ca_certs: Vec<PathBuf>,
+    /// Client certificate, PEM if `client_key` is given and PKCS#12 otherwise
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub client_cert_password: Option<String>,
+    pub user_agent: Option<String>,
+    /// How much of the traffic to log on standard error
+    pub log_level: trace::Level,
+    /// File to append every request and response to, as JSON lines
+    pub trace_file: Option<PathBuf>,
+    /// Record responses to a directory, or answer from one instead of the network
+    pub fixtures: Option<fixture::Mode>,
+}
+
+fn read(path: &Path, what: &str) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("Could not read {:} {:}", what, path.display()))
+}
+
+/// The HTTP client, with the proxy and TLS settings applied
+fn client(options: &Options) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(options.timeout)
+        .user_agent(options.user_agent.as_deref().unwrap_or(USER_AGENT));
+
+    if let Some(url) = &options.proxy {
+        let proxy =
+            reqwest::Proxy::all(url).with_context(|| format!("Invalid proxy URL {:}", url))?;
+        let no_proxy = options
+            .no_proxy
+            .as_deref()
+            .and_then(reqwest::NoProxy::from_string);
+        builder = builder.proxy(proxy.no_proxy(no_proxy));
+    } else if let Some(hosts) = &options.no_proxy {
+        // reqwest only applies `$NO_PROXY` to the proxies it takes from the environment, so
+        // take them ourselves to add our hosts to it
+        let hosts = match config::env_or("NO_PROXY", config::env_or("no_proxy", None)) {
+            Some(env) => format!("{:},{:}", env, hosts),
+            None => hosts.clone(),
+        };
+        let no_proxy = || reqwest::NoProxy::from_string(&hosts);
+        for (scheme, vars) in [
+            ("https", ["HTTPS_PROXY", "https_proxy"]),
+            ("http", ["HTTP_PROXY", "http_proxy"]),
+            ("all", ["ALL_PROXY", "all_proxy"]),
+        ] {
+            let url = match vars.iter().find_map(|v| config::env_or(v, None)) {
+                Some(url) => url,
+                None => continue,
+            };
+            let proxy = match scheme {
+                "https" => reqwest::Proxy::https(&url),
+                "http" => reqwest::Proxy::http(&url),
+                _ => reqwest::Proxy::all(&url),
+            }
+            .with_context(|| format!("Invalid proxy URL {:}", url))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy()));
+        }
+    }
+
+    for path in options.ca_certs.iter() {
+        let certificates = reqwest::Certificate::from_pem_bundle(&read(path, "CA bundle")?)
+            .with_context(|| format!("Invalid CA bundle {:}", path.display()))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    if let Some(path) = &options.client_cert {
+        let cert = read(path, "client certificate")?;
+        let identity = match &options.client_key {
+            Some(key) => reqwest::Identity::from_pkcs8_pem(&cert, &read(key, "client key")?),
+            None => reqwest::Identity::from_pkcs12_der(
+                &cert,
+                options.client_cert_password.as_deref().unwrap_or_default(),
+            ),
+        }
+        .with_context(|| format!("Invalid client certificate {:}", path.display()))?;
+        builder = builder.identity(identity);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Whether the operation to run changes something. Anything that can't be parsed counts as
+/// a mutation, so it isn't retried by mistake.
+fn is_mutation(query: &str, operation: Option<&str>) -> bool {
+    let document = match graphql_parser::parse_query::<&str>(query) {
+        Ok(document) => document,
+        Err(_) => return true,
+    };
+    let operations: Vec<&OperationDefinition<&str>> = document
+        .definitions
+        .iter()
+        .filter_map(|d| match d {
+            Definition::Operation(o) => Some(o),
+            Definition::Fragment(_) => None,
+        })
+        .collect();
+    let selected = match operation {
+        Some(name) => operations.iter().find(|o| match o {
+            OperationDefinition::Query(q) => q.name == Some(name),
+            OperationDefinition::Mutation(m) => m.name == Some(name),
+            OperationDefinition::Subscription(s) => s.name == Some(name),
+            OperationDefinition::SelectionSet(_) => false,
+        }),
+        None if operations.len() == 1 => operations.first(),
+        None => None,
+    };
+    !matches!(
+        selected,
+        Some(OperationDefinition::Query(_)) | Some(OperationDefinition::SelectionSet(_))
+    )
+}
+
+/// A response read in full
+struct Reply {
+    status: reqwest::StatusCode,
+    text: String,
+}
+
+/// Thin wrapper around the HTTP client that knows how to talk to the GraphQL endpoint
+pub struct Api {
+    client: reqwest::Client,
+    token: String,
+    endpoint: String,
+    retries: u32,
+    retry_mutations: bool,
+    tracer: Tracer,
+    fixtures: Option<Fixtures>,
+}
+
+impl Api {
+    pub fn new(token: String, options: Options) -> Result<Api> {
+        Ok(Api {
+            client: client(&options)?,
+            tracer: Tracer::new(options.log_level, options.trace_file.as_deref(), &token)?,
+            fixtures: options.fixtures.map(Fixtures::new).transpose()?,
+            token,
+            endpoint: options.endpoint.unwrap_or_else(|| ENDPOINT.to_string()),
+            retries: options.retries,
+            retry_mutations: options.retry_mutations,
+        })
+    }
+
+    /// Post a request body, sending it again after transient failures if that's safe
+    async fn send<B: Serialize + ?Sized>(
+        &self,
+        body: &B,
+        name: &str,
+        mutation: bool,
+    ) -> Result<Reply> {
+        let body = serde_json::to_value(body)?;
+        if let Some(fixtures) = &self.fixtures {
+            if let Some((status, text)) = fixtures.replay(name, &body)? {
+                return Ok(Reply { status, text });
+            }
+        }
+
+        let retries = if mutation && !self.retry_mutations {
+            0
+        } else {
+            self.retries
+        };
+        let mut attempt = 0;
+        loop {
+            self.tracer.request(name, &body);
+            let start = Instant::now();
+            let result = self
+                .client
+                .post(&self.endpoint)
+                .bearer_auth(&self.token)
+                .json(&body)
+                .send()
+                .await;
+
+            // Read the whole response here, so it can be logged
+            let result = match result {
+                Ok(res) => {
+                    let status = res.status();
+                    let headers = res.headers().clone();
+                    res.text().await.map(|text| (status, headers, text))
+                }
+                Err(e) => Err(e),
+            };
+            let outcome = match &result {
+                Ok((status, _, text)) => trace::Outcome::Response {
+                    status: *status,
+                    body: text,
+                },
+                Err(e) => trace::Outcome::Failed(e),
+            };
+            self.tracer
+                .exchange(name, &self.endpoint, &body, start.elapsed(), outcome);
+
+            let (wait, reason) = match &result {
+                Ok((status, headers, _)) if retry::is_transient(*status) => {
+                    (retry::delay(attempt, Some(headers)), status.to_string())
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    (retry::delay(attempt, None), e.to_string())
+                }
+                _ => (None, String::new()),
+            };
+            match wait {
+                Some(wait) if attempt < retries => {
+                    eprintln!(
+                        "{:} {:} in {:.1}s ({:})",
+                        "Retrying".yellow(),
+                        name,
+                        wait.as_secs_f64(),
+                        reason
+                    );
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+                _ => {
+                    let (status, _, text) = result?;
+                    if let Some(fixtures) = &self.fixtures {
+                        fixtures.record(name, &body, status, &text)?;
+                    }
+                    return Ok(Reply { status, text });
+                }
+            }
+        }
+    }
+
+    /// Send a single query or mutation and return its data
+    pub async fn query<Q: GraphQLQuery>(&self, variables: Q::Variables) -> Result<Q::ResponseData> {
+        let q = Q::build_query(variables);
+
+        let mutation = is_mutation(q.query, Some(q.operation_name));
+        let reply = self.send(&q, q.operation_name, mutation).await?;
+
+        if !reply.status.is_success() {
+            return Err(anyhow!("{} failed: {}", q.operation_name, reply.status));
+        }
+
+        let response_json: Response<Q::ResponseData> = serde_json::from_str(&reply.text)?;
+        match response_json.data {
+            Some(data) => Ok(data),
+            None => {
+                let messages: Vec<String> = response_json
+                    .errors
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|e| e.message)
+                    .collect();
+                Err(QueryError {
+                    operation: q.operation_name.to_string(),
+                    messages,
+                }
+                .into())
+            }
+        }
+    }
+
+    /// Send a request body as is and return the whole JSON response, errors included
+    pub async fn raw(&self, body: &serde_json::Value) -> Result<serde_json::Value> {
+        let query = body["query"].as_str().unwrap_or_default();
+        let operation = body["operationName"].as_str();
+        let reply = self
+            .send(
+                body,
+                operation.unwrap_or("query"),
+                is_mutation(query, operation),
+            )
+            .await?;
+
+        // GraphQL errors often come with a 4xx status, but they are worth showing as is
+        serde_json::from_str(&reply.text).map_err(|_| anyhow!("{:}: {:}", reply.status, reply.text))
+    }
+
+    /// Fetch work items in a space page by page, stopping after `limit` items if given
+    pub async fn work_items(
+        &self,
+        space_id: &str,
+        status_filter: Option<items_query::StatusFilter>,
+        limit: Option<usize>,
+    ) -> Result<Vec<WorkItem>> {
+        let mut items = Vec::new();
+        let mut has_more = true;
+        let mut cursor: Option<String> = None;
+
+        while has_more && limit.is_none_or(|l| items.len() < l) {
+            let remaining = limit.map(|l| (l - items.len()) as i64);
+            let response_data = self
+                .query::<ItemsQuery>(items_query::Variables {
+                    space_id: space_id.to_string(),
+                    cursor,
+                    count: Some(remaining.map_or(MAX_PAGE_SIZE, |r| r.min(MAX_PAGE_SIZE))),
+                    status_filter: status_filter.clone(),
+                })
+                .await?;
+
+            has_more = response_data.work_items.has_more;
+            cursor = Some(response_data.work_items.cursor);
+            items.extend(response_data.work_items.work_items);
+        }
+
+        if let Some(l) = limit {
+            items.truncate(l);
+        }
+        Ok(items)
+    }
+}
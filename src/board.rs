@@ -0,0 +1,624 @@
+//! Full-screen kanban board for a space, `km board`
+
+use anyhow::Result;
+
+use std::collections::HashMap;
+use std::io::{stderr, Stderr, Write};
+
+use termimad::crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use termimad::{Area, MadSkin, TextView};
+
+use crate::api::{
+    add_labels_to_work_item, add_members_to_work_item, comment_on_work_item, item_query,
+    items_query, move_work_item, remove_labels_from_work_item, remove_members_from_work_item,
+    space_query, AddLabelsToWorkItem, AddMembersToWorkItem, Api, CommentOnWorkItem, ItemQuery,
+    MoveWorkItem, RemoveLabelsFromWorkItem, RemoveMembersFromWorkItem, WorkItem,
+};
+use crate::color;
+use crate::list::{self, truncate};
+use crate::picker::{self, TerminalGuard};
+use crate::resolve::{Organization, Space, Status};
+
+/// Columns narrower than this are scrolled horizontally instead
+const MIN_COLUMN_WIDTH: usize = 28;
+
+/// Each card takes a line for the title and one for labels and members
+const CARD_HEIGHT: usize = 2;
+
+const HELP: &str = "←↓↑→/hjkl navigate  enter details  m move  H/L move left/right  a assign  t label  c comment  r refresh  q quit";
+
+struct Column<'a> {
+    status: &'a Status,
+    items: Vec<WorkItem>,
+    selected: usize,
+    offset: usize,
+}
+
+struct Detail {
+    id: String,
+    scroll: usize,
+}
+
+struct Board<'a> {
+    api: &'a Api,
+    org: &'a Organization,
+    space: &'a Space,
+    columns: Vec<Column<'a>>,
+    column: usize,
+    column_offset: usize,
+    detail: Option<Detail>,
+    /// Descriptions fetched for the detail pane, by item id
+    descriptions: HashMap<String, String>,
+    message: String,
+    skin: MadSkin,
+}
+
+impl<'a> Board<'a> {
+    fn selected(&self) -> Option<&WorkItem> {
+        let column = self.columns.get(self.column)?;
+        column.items.get(column.selected)
+    }
+
+    fn key(&self, item: &WorkItem) -> String {
+        format!("{:}-{:}", self.space.key, item.number)
+    }
+
+    /// Fetch the items again, keeping the selection on the item with `keep_id` if given
+    async fn load(&mut self, keep_id: Option<String>) -> Result<()> {
+        let status_filter = items_query::StatusFilter {
+            type_: items_query::StatusFilterType::EXCLUDE,
+            filter: vec![items_query::StatusType::ARCHIVED],
+        };
+        let items = self
+            .api
+            .work_items(&self.space.id, Some(status_filter), None)
+            .await?;
+
+        for column in self.columns.iter_mut() {
+            column.items.clear();
+        }
+        for item in items {
+            if let Some(column) = self
+                .columns
+                .iter_mut()
+                .find(|c| c.status.id == item.status.id)
+            {
+                column.items.push(item);
+            }
+        }
+        for column in self.columns.iter_mut() {
+            column.items.sort_by(|a, b| a.sort.cmp(&b.sort));
+            column.selected = column.selected.min(column.items.len().saturating_sub(1));
+        }
+
+        if let Some(id) = keep_id {
+            for (c, column) in self.columns.iter_mut().enumerate() {
+                if let Some(i) = column.items.iter().position(|item| item.id == id) {
+                    column.selected = i;
+                    self.column = c;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, out: &mut Stderr) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            SetAttribute(Attribute::Bold),
+            Print(truncate(&self.space.name, width)),
+            SetAttribute(Attribute::Reset),
+        )?;
+        let help_at = self.space.name.chars().count() + 3;
+        if help_at < width {
+            queue!(
+                out,
+                cursor::MoveTo(help_at as u16, 0),
+                SetAttribute(Attribute::Dim),
+                Print(truncate(HELP, width - help_at)),
+                SetAttribute(Attribute::Reset),
+            )?;
+        }
+
+        // The board takes the top, the detail pane (if open) the bottom half
+        let bottom = height.saturating_sub(1);
+        let board_height = if self.detail.is_some() {
+            bottom / 2
+        } else {
+            bottom
+        };
+        self.draw_columns(out, width, 1, board_height.saturating_sub(1))?;
+        if self.detail.is_some() {
+            self.draw_detail(out, width, board_height, bottom - board_height)?;
+        }
+
+        queue!(
+            out,
+            cursor::MoveTo(0, bottom as u16),
+            Print(truncate(&self.message, width)),
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn draw_columns(
+        &mut self,
+        out: &mut Stderr,
+        width: usize,
+        top: usize,
+        height: usize,
+    ) -> Result<()> {
+        let visible = (width / MIN_COLUMN_WIDTH).clamp(1, self.columns.len().max(1));
+        if self.column < self.column_offset {
+            self.column_offset = self.column;
+        } else if self.column >= self.column_offset + visible {
+            self.column_offset = self.column + 1 - visible;
+        }
+        let column_width = width / visible;
+        let cards = height.saturating_sub(1) / CARD_HEIGHT;
+
+        for (i, column) in self
+            .columns
+            .iter_mut()
+            .enumerate()
+            .skip(self.column_offset)
+            .take(visible)
+        {
+            let left = ((i - self.column_offset) * column_width) as u16;
+            let inner = column_width.saturating_sub(2);
+            let current = i == self.column;
+
+            queue!(
+                out,
+                cursor::MoveTo(left, top as u16),
+                SetAttribute(Attribute::Bold)
+            )?;
+            if current {
+                queue!(out, SetAttribute(Attribute::Underlined))?;
+            }
+            queue!(
+                out,
+                Print(truncate(
+                    &format!("{:} ({:})", column.status.name, column.items.len()),
+                    inner
+                )),
+                SetAttribute(Attribute::Reset),
+            )?;
+
+            if column.selected < column.offset {
+                column.offset = column.selected;
+            } else if cards > 0 && column.selected >= column.offset + cards {
+                column.offset = column.selected + 1 - cards;
+            }
+
+            for (row, (index, item)) in column
+                .items
+                .iter()
+                .enumerate()
+                .skip(column.offset)
+                .take(cards)
+                .enumerate()
+            {
+                let y = (top + 1 + row * CARD_HEIGHT) as u16;
+                let title = truncate(&format!("{:} {:}", item.number, item.title), inner);
+
+                queue!(out, cursor::MoveTo(left, y))?;
+                if current && index == column.selected {
+                    queue!(
+                        out,
+                        SetAttribute(Attribute::Reverse),
+                        Print(format!("{:<width$}", title, width = inner)),
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                } else {
+                    queue!(out, Print(title))?;
+                }
+
+                // Label chips tinted with their color, then members
+                queue!(out, cursor::MoveTo(left, y + 1))?;
+                let mut used = 0;
+                for label in item.labels.iter() {
+                    let chip = format!("{:} ", label.name);
+                    if used + chip.chars().count() > inner {
+                        break;
+                    }
+                    let (r, g, b) = color::label_rgb(&label.color);
+                    queue!(
+                        out,
+                        SetForegroundColor(Color::Rgb { r, g, b }),
+                        SetAttribute(Attribute::Italic),
+                        Print(&chip),
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                    used += chip.chars().count();
+                }
+                let members: Vec<String> = item
+                    .members
+                    .iter()
+                    .map(|m| format!("@{:}", m.username))
+                    .collect();
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Dim),
+                    Print(truncate(&members.join(" "), inner.saturating_sub(used))),
+                    SetAttribute(Attribute::Reset),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_detail(&self, out: &mut Stderr, width: usize, top: usize, height: usize) -> Result<()> {
+        let (item, detail) = match (self.selected(), &self.detail) {
+            (Some(item), Some(detail)) => (item, detail),
+            _ => return Ok(()),
+        };
+
+        queue!(
+            out,
+            cursor::MoveTo(0, top as u16),
+            Print("─".repeat(width)),
+            cursor::MoveTo(0, top as u16 + 1),
+            SetAttribute(Attribute::Bold),
+            Print(truncate(
+                &format!("{:}: {:}", self.key(item), item.title),
+                width
+            )),
+            SetAttribute(Attribute::Reset),
+            cursor::MoveTo(0, top as u16 + 2),
+            Print(truncate(
+                &format!(
+                    "Status: {:}   Members: {:}",
+                    item.status.name,
+                    item.members
+                        .iter()
+                        .map(|m| m.username.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                ),
+                width
+            )),
+        )?;
+
+        let description = self
+            .descriptions
+            .get(&detail.id)
+            .map(String::as_str)
+            .unwrap_or("");
+        if height > 4 && width > 2 {
+            let area = Area::new(0, top as u16 + 4, width as u16, (height - 4) as u16);
+            let text = self.skin.area_text(description, &area);
+            let mut view = TextView::from(&area, &text);
+            view.scroll = detail.scroll;
+            view.write_on(out)?;
+        }
+        Ok(())
+    }
+
+    /// Read a line of text on the bottom row. `None` if cancelled.
+    fn input(&mut self, prompt: &str) -> Result<Option<String>> {
+        let mut text = String::new();
+        let mut out = stderr();
+        loop {
+            self.message = format!("{:}: {:}_", prompt, text);
+            self.draw(&mut out)?;
+            if let Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            }) = event::read()?
+            {
+                match code {
+                    KeyCode::Esc => return Ok(None),
+                    KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(None)
+                    }
+                    KeyCode::Enter => return Ok(Some(text)),
+                    KeyCode::Backspace => {
+                        text.pop();
+                    }
+                    KeyCode::Char(c) => text.push(c),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    async fn show_detail(&mut self) -> Result<()> {
+        let id = match self.selected() {
+            Some(item) => item.id.clone(),
+            None => return Ok(()),
+        };
+        if !self.descriptions.contains_key(&id) {
+            let response_data = self
+                .api
+                .query::<ItemQuery>(item_query::Variables {
+                    item_id: id.clone(),
+                })
+                .await?;
+            self.descriptions
+                .insert(id.clone(), response_data.work_item.description);
+        }
+        self.detail = Some(Detail { id, scroll: 0 });
+        Ok(())
+    }
+
+    async fn move_to(&mut self, status_index: usize) -> Result<()> {
+        let (id, key) = match self.selected() {
+            Some(item) => (item.id.clone(), self.key(item)),
+            None => return Ok(()),
+        };
+        let status = self.columns[status_index].status;
+        self.api
+            .query::<MoveWorkItem>(move_work_item::Variables {
+                id: id.clone(),
+                status_id: status.id.clone(),
+            })
+            .await?;
+        self.load(Some(id)).await?;
+        self.message = format!("Moved {:} to {:}", key, status.name);
+        Ok(())
+    }
+
+    async fn pick_move(&mut self) -> Result<()> {
+        if self.selected().is_none() {
+            return Ok(());
+        }
+        let names: Vec<String> = self.columns.iter().map(|c| c.status.name.clone()).collect();
+        if let Some(i) = picker::run("Move to", &names)? {
+            self.move_to(i).await?;
+        }
+        Ok(())
+    }
+
+    /// Pick a user and add them as a member, or remove them if they already are one
+    async fn toggle_member(&mut self) -> Result<()> {
+        let item = match self.selected() {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        let (id, key) = (item.id.clone(), self.key(item));
+        let current: Vec<String> = item.members.iter().map(|m| m.id.clone()).collect();
+
+        let users: Vec<_> = self.org.users.iter().filter(|u| !u.deactivated).collect();
+        let names: Vec<String> = users
+            .iter()
+            .map(|u| {
+                let marker = if current.contains(&u.id) { "✓" } else { " " };
+                format!("{:} {:}", marker, u.username)
+            })
+            .collect();
+        let user = match picker::run("Assign", &names)? {
+            Some(i) => users[i],
+            None => return Ok(()),
+        };
+
+        if current.contains(&user.id) {
+            self.api
+                .query::<RemoveMembersFromWorkItem>(remove_members_from_work_item::Variables {
+                    id: id.clone(),
+                    member_ids: vec![user.id.clone()],
+                })
+                .await?;
+            self.message = format!("Removed {:} from {:}", user.username, key);
+        } else {
+            self.api
+                .query::<AddMembersToWorkItem>(add_members_to_work_item::Variables {
+                    id: id.clone(),
+                    member_ids: vec![user.id.clone()],
+                })
+                .await?;
+            self.message = format!("Assigned {:} to {:}", user.username, key);
+        }
+        self.load(Some(id)).await
+    }
+
+    /// Pick a label and add it, or remove it if the item already has it
+    async fn toggle_label(&mut self) -> Result<()> {
+        let item = match self.selected() {
+            Some(item) => item,
+            None => return Ok(()),
+        };
+        let (id, key) = (item.id.clone(), self.key(item));
+        let current: Vec<String> = item.labels.iter().map(|l| l.id.clone()).collect();
+
+        let names: Vec<String> = self
+            .space
+            .labels
+            .iter()
+            .map(|l| {
+                let marker = if current.contains(&l.id) { "✓" } else { " " };
+                format!("{:} {:}", marker, l.name)
+            })
+            .collect();
+        let label = match picker::run("Label", &names)? {
+            Some(i) => &self.space.labels[i],
+            None => return Ok(()),
+        };
+
+        if current.contains(&label.id) {
+            self.api
+                .query::<RemoveLabelsFromWorkItem>(remove_labels_from_work_item::Variables {
+                    id: id.clone(),
+                    label_ids: vec![label.id.clone()],
+                })
+                .await?;
+            self.message = format!("Removed label {:} from {:}", label.name, key);
+        } else {
+            self.api
+                .query::<AddLabelsToWorkItem>(add_labels_to_work_item::Variables {
+                    id: id.clone(),
+                    label_ids: vec![label.id.clone()],
+                })
+                .await?;
+            self.message = format!("Added label {:} to {:}", label.name, key);
+        }
+        self.load(Some(id)).await
+    }
+
+    async fn comment(&mut self) -> Result<()> {
+        let (id, key) = match self.selected() {
+            Some(item) => (item.id.clone(), self.key(item)),
+            None => return Ok(()),
+        };
+        let body = match self.input(&format!("Comment on {:}", key))? {
+            Some(body) if !body.trim().is_empty() => body,
+            _ => {
+                self.message.clear();
+                return Ok(());
+            }
+        };
+        self.api
+            .query::<CommentOnWorkItem>(comment_on_work_item::Variables { id, body })
+            .await?;
+        self.message = format!("Commented on {:}", key);
+        Ok(())
+    }
+
+    /// Handle a key press, returning false when the board should close
+    async fn handle(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Result<bool> {
+        let shift = modifiers.contains(KeyModifiers::SHIFT);
+        let last_column = self.columns.len().saturating_sub(1);
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc if self.detail.is_none() => return Ok(false),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(false),
+            KeyCode::Char('q') | KeyCode::Esc => self.detail = None,
+
+            KeyCode::Char('H') if self.column > 0 => self.move_to(self.column - 1).await?,
+            KeyCode::Left if shift && self.column > 0 => self.move_to(self.column - 1).await?,
+            KeyCode::Char('L') if self.column < last_column => {
+                self.move_to(self.column + 1).await?
+            }
+            KeyCode::Right if shift && self.column < last_column => {
+                self.move_to(self.column + 1).await?
+            }
+
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(last_column),
+            KeyCode::Up | KeyCode::Char('k') => {
+                if let Some(column) = self.columns.get_mut(self.column) {
+                    column.selected = column.selected.saturating_sub(1);
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if let Some(column) = self.columns.get_mut(self.column) {
+                    column.selected =
+                        (column.selected + 1).min(column.items.len().saturating_sub(1));
+                }
+            }
+            KeyCode::Char('g') | KeyCode::Home => {
+                if let Some(column) = self.columns.get_mut(self.column) {
+                    column.selected = 0;
+                }
+            }
+            KeyCode::Char('G') | KeyCode::End => {
+                if let Some(column) = self.columns.get_mut(self.column) {
+                    column.selected = column.items.len().saturating_sub(1);
+                }
+            }
+
+            KeyCode::PageDown | KeyCode::Char('J') => {
+                if let Some(detail) = self.detail.as_mut() {
+                    detail.scroll += 5;
+                }
+            }
+            KeyCode::PageUp | KeyCode::Char('K') => {
+                if let Some(detail) = self.detail.as_mut() {
+                    detail.scroll = detail.scroll.saturating_sub(5);
+                }
+            }
+
+            KeyCode::Enter | KeyCode::Char('d') => {
+                if self.detail.is_some() {
+                    self.detail = None;
+                } else {
+                    self.show_detail().await?;
+                }
+            }
+            KeyCode::Char('m') => self.pick_move().await?,
+            KeyCode::Char('a') => self.toggle_member().await?,
+            KeyCode::Char('t') => self.toggle_label().await?,
+            KeyCode::Char('c') => self.comment().await?,
+            KeyCode::Char('r') => {
+                let keep = self.selected().map(|i| i.id.clone());
+                self.load(keep).await?;
+                self.message = "Refreshed".to_string();
+            }
+            _ => {}
+        }
+
+        // Keep the detail pane following the selection
+        if self.detail.is_some() {
+            let selected = self.selected().map(|i| i.id.clone());
+            if selected.as_ref() != self.detail.as_ref().map(|d| &d.id) {
+                self.show_detail().await?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Show the board for a space until the user quits
+pub async fn run(api: &Api, org: &Organization, space: &Space) -> Result<()> {
+    let mut statuses: Vec<&Status> = space
+        .statuses
+        .iter()
+        .filter(|s| s.type_ != space_query::StatusType::ARCHIVED)
+        .collect();
+    statuses.sort_by_key(|s| list::status_type_rank(&(&s.type_).into()));
+
+    let mut board = Board {
+        api,
+        org,
+        space,
+        columns: statuses
+            .into_iter()
+            .map(|status| Column {
+                status,
+                items: Vec::new(),
+                selected: 0,
+                offset: 0,
+            })
+            .collect(),
+        column: 0,
+        column_offset: 0,
+        detail: None,
+        descriptions: HashMap::new(),
+        message: String::new(),
+        skin: MadSkin::default(),
+    };
+    board.load(None).await?;
+
+    let _guard = TerminalGuard::new()?;
+    let mut out = stderr();
+    loop {
+        board.draw(&mut out)?;
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            // Show API errors in the status line rather than tearing down the board
+            match board.handle(code, modifiers).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => board.message = format!("Error: {:}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Applying one change to many work items at once, `km item bulk`
+
+use anyhow::{anyhow, bail, Result};
+
+use colored::*;
+
+use futures::stream::{self, StreamExt};
+
+use structopt::clap::ArgGroup;
+use structopt::StructOpt;
+
+use std::collections::HashMap;
+
+use crate::api::{
+    add_labels_to_work_item, add_members_to_work_item, edit_work_item, move_work_item,
+    remove_labels_from_work_item, remove_members_from_work_item, AddLabelsToWorkItem,
+    AddMembersToWorkItem, Api, EditWorkItem, MoveWorkItem, RemoveLabelsFromWorkItem,
+    RemoveMembersFromWorkItem,
+};
+use crate::config::Config;
+use crate::list::Row;
+use crate::picker;
+use crate::resolve::{self, Organization};
+
+/// Work items shown before asking for confirmation
+const SAMPLE: usize = 5;
+
+#[derive(StructOpt, Debug)]
+pub enum Action {
+    /// Move the work items to another status
+    Move {
+        /// Name of the target status, which every space involved must have
+        status: String,
+    },
+
+    /// Add a label to (or remove one from) the work items
+    Label {
+        /// Label name, which every space involved must have
+        label: String,
+        /// Remove the label instead
+        #[structopt(short, long)]
+        remove: bool,
+    },
+
+    /// Add a member to (or remove one from) the work items
+    Assign {
+        /// Username, or "me"
+        user: String,
+        /// Remove the member instead
+        #[structopt(short, long)]
+        remove: bool,
+    },
+
+    /// Change the effort or impact of the work items
+    #[structopt(group = ArgGroup::with_name("change").required(true).multiple(true))]
+    Edit {
+        /// New effort, e.g. S, M or L
+        #[structopt(long, group = "change")]
+        effort: Option<String>,
+        /// New impact, e.g. LOW, MEDIUM or HIGH
+        #[structopt(long, group = "change")]
+        impact: Option<String>,
+    },
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::Move { status } => format!("Move to {:}", status.bold()),
+            Action::Label {
+                label,
+                remove: false,
+            } => format!("Add label {:} to", label.bold()),
+            Action::Label {
+                label,
+                remove: true,
+            } => format!("Remove label {:} from", label.bold()),
+            Action::Assign {
+                user,
+                remove: false,
+            } => format!("Assign {:} to", user.bold()),
+            Action::Assign { user, remove: true } => format!("Remove {:} from", user.bold()),
+            Action::Edit { effort, impact } => {
+                let mut changes = Vec::new();
+                if let Some(effort) = effort {
+                    changes.push(format!("effort {:}", effort.bold()));
+                }
+                if let Some(impact) = impact {
+                    changes.push(format!("impact {:}", impact.bold()));
+                }
+                format!("Set {:} on", changes.join(" and "))
+            }
+        }
+    }
+}
+
+/// A work item to change
+pub struct Target {
+    pub number: String,
+    pub id: String,
+    pub space_key: String,
+    pub title: String,
+}
+
+impl From<Row> for Target {
+    fn from(row: Row) -> Self {
+        Target {
+            number: row.key(),
+            id: row.item.id,
+            space_key: row.space_key,
+            title: row.item.title,
+        }
+    }
+}
+
+/// Look up work items by number, keeping their order
+pub async fn targets(api: &Api, numbers: Vec<String>, jobs: usize) -> Result<Vec<Target>> {
+    stream::iter(numbers)
+        .map(|number| async move {
+            let item = resolve::item(api, &number)
+                .await
+                .map_err(|e| anyhow!("{:}: {:}", number, e))?;
+            Ok(Target {
+                number: format!("{:}-{:}", item.space.key, item.number),
+                id: item.id,
+                space_key: item.space.key,
+                title: item.title,
+            })
+        })
+        .buffered(jobs.max(1))
+        .collect::<Vec<Result<Target>>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// The action for a single space, with names resolved to ids
+enum Change {
+    Move(String),
+    AddLabel(String),
+    RemoveLabel(String),
+    AddMember(String),
+    RemoveMember(String),
+    Edit(Option<String>, Option<String>),
+}
+
+fn change(org: &Organization, config: &Config, space_key: &str, action: &Action) -> Result<Change> {
+    let spc = resolve::space(org, space_key)?;
+    Ok(match action {
+        Action::Move { status } => Change::Move(resolve::status(spc, Some(status))?.id.clone()),
+        Action::Label { label, remove } => {
+            let id = resolve::label(spc, Some(label))?.id.clone();
+            if *remove {
+                Change::RemoveLabel(id)
+            } else {
+                Change::AddLabel(id)
+            }
+        }
+        Action::Assign { user, remove } => {
+            let id = resolve::user(org, config, Some(user))?.id.clone();
+            if *remove {
+                Change::RemoveMember(id)
+            } else {
+                Change::AddMember(id)
+            }
+        }
+        Action::Edit { effort, impact } => Change::Edit(effort.clone(), impact.clone()),
+    })
+}
+
+async fn apply(api: &Api, change: &Change, id: String) -> Result<()> {
+    match change {
+        Change::Move(status_id) => {
+            api.query::<MoveWorkItem>(move_work_item::Variables {
+                id,
+                status_id: status_id.clone(),
+            })
+            .await?;
+        }
+        Change::AddLabel(label_id) => {
+            api.query::<AddLabelsToWorkItem>(add_labels_to_work_item::Variables {
+                id,
+                label_ids: vec![label_id.clone()],
+            })
+            .await?;
+        }
+        Change::RemoveLabel(label_id) => {
+            api.query::<RemoveLabelsFromWorkItem>(remove_labels_from_work_item::Variables {
+                id,
+                label_ids: vec![label_id.clone()],
+            })
+            .await?;
+        }
+        Change::AddMember(member_id) => {
+            api.query::<AddMembersToWorkItem>(add_members_to_work_item::Variables {
+                id,
+                member_ids: vec![member_id.clone()],
+            })
+            .await?;
+        }
+        Change::RemoveMember(member_id) => {
+            api.query::<RemoveMembersFromWorkItem>(remove_members_from_work_item::Variables {
+                id,
+                member_ids: vec![member_id.clone()],
+            })
+            .await?;
+        }
+        Change::Edit(effort, impact) => {
+            api.query::<EditWorkItem>(edit_work_item::Variables {
+                id,
+                title: None,
+                description: None,
+                effort: effort.clone(),
+                impact: impact.clone(),
+            })
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Confirm and apply the action to every target, `jobs` at a time
+pub async fn run(
+    api: &Api,
+    org: &Organization,
+    config: &Config,
+    targets: Vec<Target>,
+    action: &Action,
+    yes: bool,
+    jobs: usize,
+) -> Result<()> {
+    if targets.is_empty() {
+        println!("No work items to change");
+        return Ok(());
+    }
+
+    // Resolve names up front, so a space without the status or label stops us before we start
+    let mut changes: HashMap<&str, Change> = HashMap::new();
+    for target in targets.iter() {
+        if !changes.contains_key(target.space_key.as_str()) {
+            let change = change(org, config, &target.space_key, action)?;
+            changes.insert(&target.space_key, change);
+        }
+    }
+
+    println!("{:} {:} work items:", action.describe(), targets.len());
+    for target in targets.iter().take(SAMPLE) {
+        println!("  {:<12}{:}", target.number.yellow(), target.title);
+    }
+    if targets.len() > SAMPLE {
+        println!("  … and {:} more", targets.len() - SAMPLE);
+    }
+    if !yes && !picker::confirm("Continue?")? {
+        bail!("Cancelled");
+    }
+
+    let total = targets.len();
+    let mut results = stream::iter(targets.iter())
+        .map(|target| {
+            let change = &changes[target.space_key.as_str()];
+            async move { (target, apply(api, change, target.id.clone()).await) }
+        })
+        .buffer_unordered(jobs.max(1));
+
+    let mut failures = Vec::new();
+    let mut done = 0;
+    while let Some((target, result)) = results.next().await {
+        done += 1;
+        match result {
+            Ok(()) => eprintln!("[{:}/{:}] {:}", done, total, target.number.bold()),
+            Err(e) => {
+                eprintln!(
+                    "[{:}/{:}] {:} {:}: {:}",
+                    done,
+                    total,
+                    "Failed".red(),
+                    target.number.bold(),
+                    e
+                );
+                failures.push(target.number.as_str());
+            }
+        }
+    }
+
+    summary(total, &failures)
+}
+
+/// Print how many changes succeeded, failing if any didn't
+fn summary(total: usize, failures: &[&str]) -> Result<()> {
+    println!(
+        "\n{:} succeeded, {:} failed",
+        (total - failures.len()).to_string().green(),
+        if failures.is_empty() {
+            "0".normal()
+        } else {
+            failures.len().to_string().red()
+        }
+    );
+    if !failures.is_empty() {
+        bail!("Failed for {:}", failures.join(", "));
+    }
+    Ok(())
+}
+
+/// Outcomes of handling work items one after another, as `km item move`, `view` and the
+/// like do with several numbers: a failure is reported and the rest are still handled
+pub struct Tally {
+    total: usize,
+    failures: Vec<(String, anyhow::Error)>,
+}
+
+impl Tally {
+    pub fn new(total: usize) -> Tally {
+        Tally {
+            total,
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, number: &str, result: Result<()>) {
+        if let Err(e) = result {
+            if self.total > 1 {
+                eprintln!("{:} {:}: {:}", "Failed".red(), number.bold(), e);
+            }
+            self.failures.push((number.to_string(), e));
+        }
+    }
+
+    /// With a single work item its error is returned as is, otherwise there's a summary
+    pub fn finish(mut self) -> Result<()> {
+        if self.total <= 1 {
+            return match self.failures.pop() {
+                Some((_, e)) => Err(e),
+                None => Ok(()),
+            };
+        }
+        let failures: Vec<&str> = self.failures.iter().map(|(n, _)| n.as_str()).collect();
+        summary(self.total, &failures)
+    }
+}
@@ -0,0 +1,47 @@
+//! A small on-disk cache of API results, kept as JSON files in `$KM_CACHE_DIR` or
+//! `<cache dir>/km`
+
+use anyhow::Result;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// The organization's spaces, statuses, labels and users
+pub const ORGANIZATION: &str = "organization";
+
+pub fn dir() -> PathBuf {
+    match std::env::var_os("KM_CACHE_DIR") {
+        Some(p) => PathBuf::from(p),
+        None => dirs::cache_dir().unwrap_or_default().join("km"),
+    }
+}
+
+fn path(name: &str) -> PathBuf {
+    dir().join(format!("{:}.json", name))
+}
+
+/// Read a cached value, ignoring it if it is older than `max_age`. A missing or
+/// unreadable cache file is treated as a cache miss.
+pub fn load<T: DeserializeOwned>(name: &str, max_age: Option<Duration>) -> Option<T> {
+    let path = path(name);
+    if let Some(max_age) = max_age {
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age > max_age {
+            return None;
+        }
+    }
+    let text = fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn store<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    fs::create_dir_all(dir())?;
+    fs::write(path(name), serde_json::to_string(value)?)?;
+    Ok(())
+}
@@ -0,0 +1,347 @@
+//! Release notes for `km changelog`: the work items closed in a date range, grouped by
+//! label or initiative and written out with a template
+//!
+//! A template is text with `{{placeholders}}` and `{{#section}}...{{/section}}` blocks
+//! repeated for each group or work item. The default is:
+//!
+//! ```text
+//! ## {{heading}}
+//!
+//! {{#groups}}
+//! ### {{group}}
+//!
+//! {{#items}}
+//! - {{title}} ({{key}})
+//! {{/items}}
+//!
+//! {{/groups}}
+//! ```
+//!
+//! * At the top: `heading`, `since`, `until`, `count`, and the `groups` and `items` sections
+//! * In `groups`: `group`, `count` and the group's `items`
+//! * In `items`: `key`, `title`, `status`, `space`, `labels`, `members`, `initiatives`,
+//!   `closed`
+//!
+//! A section tag on a line of its own takes the line with it, so it leaves no blank line.
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use chrono::{DateTime, Duration, Local, Utc};
+
+use regex::Regex;
+
+use std::fs;
+use std::path::Path;
+
+use crate::api::Date;
+use crate::filter;
+use crate::git;
+use crate::list::{self, GroupBy, Row};
+
+const DEFAULT_TEMPLATE: &str = "## {{heading}}
+
+{{#groups}}
+### {{group}}
+
+{{#items}}
+- {{title}} ({{key}})
+{{/items}}
+
+{{/groups}}
+";
+
+/// The default without `--group-by`'s sections, for `--group-by none`
+const UNGROUPED_TEMPLATE: &str = "## {{heading}}
+
+{{#items}}
+- {{title}} ({{key}})
+{{/items}}
+";
+
+/// Heading of the group of items without a label or initiative
+const OTHER: &str = "Other changes";
+
+/// The template from a file, or the default for the grouping
+pub fn template(path: Option<&Path>, group_by: GroupBy) -> Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Could not read template {:}", path.display())),
+        None if group_by == GroupBy::None => Ok(UNGROUPED_TEMPLATE.to_string()),
+        None => Ok(DEFAULT_TEMPLATE.to_string()),
+    }
+}
+
+/// The local date of a point in time, as `--since` and `--until` are in local time
+fn day(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d").to_string()
+}
+
+/// The last day of a range ending at `until`, which is exclusive
+fn last_day(until: DateTime<Utc>) -> String {
+    day(until - Duration::milliseconds(1))
+}
+
+/// The range covered by `--since`/`--from-tag` and `--until`/`--to-tag`, the end exclusive
+pub fn range(
+    since: Option<&str>,
+    until: Option<&str>,
+    from_tag: Option<&str>,
+    to_tag: Option<&str>,
+) -> Result<(DateTime<Utc>, Option<DateTime<Utc>>)> {
+    let since = match (since, from_tag) {
+        (Some(since), _) => filter::parse_date(since)?.0,
+        (None, Some(tag)) => git::tag_date(tag)?,
+        (None, None) => bail!("Either --since or --from-tag is required"),
+    };
+    let until = match (until, to_tag) {
+        (Some(until), _) => Some(filter::parse_date(until)?.1),
+        (None, Some(tag)) => Some(git::tag_date(tag)?),
+        (None, None) => None,
+    };
+    if until.is_some_and(|until| until <= since) {
+        bail!("The end of the date range is before its start");
+    }
+    Ok((since, until))
+}
+
+/// The heading of the changelog: the tag it leads up to, or the dates it covers
+pub fn heading(since: DateTime<Utc>, until: Option<DateTime<Utc>>, to_tag: Option<&str>) -> String {
+    match (to_tag, until) {
+        (Some(tag), _) => tag.to_string(),
+        (None, Some(until)) => format!("Changes from {:} to {:}", day(since), last_day(until)),
+        (None, None) => format!("Changes since {:}", day(since)),
+    }
+}
+
+/// Keep the rows closed in `[since, until)`, and with the label if given, oldest first
+pub fn closed_between(
+    mut rows: Vec<Row>,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+    label: Option<&str>,
+) -> Vec<Row> {
+    rows.retain(|row| {
+        let closed = match &row.item.closed_at {
+            Some(Date(closed)) => *closed,
+            None => return false,
+        };
+        closed >= since
+            && until.is_none_or(|until| closed < until)
+            && label.is_none_or(|label| {
+                row.item
+                    .labels
+                    .iter()
+                    .any(|l| l.name.eq_ignore_ascii_case(label))
+            })
+    });
+    rows.sort_by(|a, b| {
+        (a.item.closed_at, a.space_index, a.key()).cmp(&(b.item.closed_at, b.space_index, b.key()))
+    });
+    rows
+}
+
+/// Values for the placeholders of one level of the template, and its sections
+struct Scope {
+    values: Vec<(&'static str, String)>,
+    sections: Vec<(&'static str, Vec<Scope>)>,
+}
+
+fn item_scope(row: &Row) -> Scope {
+    let item = &row.item;
+    let names = |names: Vec<&str>| names.join(", ");
+    Scope {
+        values: vec![
+            ("key", row.key()),
+            ("title", item.title.clone()),
+            ("status", item.status.name.clone()),
+            ("space", row.space_name.clone()),
+            (
+                "labels",
+                names(item.labels.iter().map(|l| l.name.as_str()).collect()),
+            ),
+            (
+                "members",
+                names(item.members.iter().map(|m| m.username.as_str()).collect()),
+            ),
+            (
+                "initiatives",
+                names(item.initiatives.iter().map(|i| i.title.as_str()).collect()),
+            ),
+            (
+                "closed",
+                item.closed_at.map(|d| d.to_string()).unwrap_or_default(),
+            ),
+        ],
+        sections: Vec::new(),
+    }
+}
+
+/// Fill in the template with the rows, grouped by `group_by`
+pub fn render(
+    template: &str,
+    heading: &str,
+    since: DateTime<Utc>,
+    until: Option<DateTime<Utc>>,
+    rows: &[Row],
+    group_by: GroupBy,
+) -> Result<String> {
+    let groups = list::groups(rows, group_by)
+        .into_iter()
+        .map(|group| Scope {
+            values: vec![
+                ("group", group.name.unwrap_or_else(|| OTHER.to_string())),
+                ("count", group.rows.len().to_string()),
+            ],
+            sections: vec![("items", group.rows.into_iter().map(item_scope).collect())],
+        })
+        .collect();
+    let top = Scope {
+        values: vec![
+            ("heading", heading.to_string()),
+            ("since", day(since)),
+            ("until", until.map(last_day).unwrap_or_default()),
+            ("count", rows.len().to_string()),
+        ],
+        sections: vec![
+            ("groups", groups),
+            ("items", rows.iter().map(item_scope).collect()),
+        ],
+    };
+
+    let standalone = Regex::new(r"(?m)^[ \t]*(\{\{[#/][a-z]+\}\})[ \t]*\r?\n").unwrap();
+    let template = standalone.replace_all(template, "$1");
+    let text = fill(&template, &[&top])?;
+    Ok(text.trim_end().to_string() + "\n")
+}
+
+/// Render `text` in the innermost of `scopes`, looking up placeholders outwards. Values
+/// are never scanned for placeholders themselves.
+fn fill(text: &str, scopes: &[&Scope]) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => bail!("Unterminated {{{{ in the template"),
+        };
+        let tag = rest[start + 2..end].trim();
+        rest = &rest[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            let close = format!("{{{{/{:}}}}}", name);
+            let (inner, after) = match rest.find(&close) {
+                Some(i) => (&rest[..i], &rest[i + close.len()..]),
+                None => bail!("Missing {:} in the template", close),
+            };
+            let children = scopes
+                .iter()
+                .rev()
+                .find_map(|s| s.sections.iter().find(|(n, _)| *n == name))
+                .map(|(_, children)| children)
+                .ok_or_else(|| anyhow!("Unknown section {{{{#{:}}}}} in the template", name))?;
+            for child in children.iter() {
+                let mut nested = scopes.to_vec();
+                nested.push(child);
+                out.push_str(&fill(inner, &nested)?);
+            }
+            rest = after;
+        } else if tag.starts_with('/') {
+            bail!("Unexpected {{{{{:}}}}} in the template", tag);
+        } else {
+            let value = scopes
+                .iter()
+                .rev()
+                .find_map(|s| s.values.iter().find(|(n, _)| *n == tag))
+                .map(|(_, value)| value)
+                .ok_or_else(|| anyhow!("Unknown placeholder {{{{{:}}}}} in the template", tag))?;
+            out.push_str(value);
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_rejects_out_of_range_dates() {
+        assert!(range(Some("99999999999999d"), None, None, None).is_err());
+        assert!(range(Some("9999999999999w"), None, None, None).is_err());
+        assert!(range(Some("7d"), Some("-99999999999999d"), None, None).is_err());
+        assert!(range(Some("2026-01-01"), Some("99999999999999h"), None, None).is_err());
+    }
+
+    #[test]
+    fn range_rejects_end_before_start() {
+        assert!(range(Some("2026-02-01"), Some("2026-01-01"), None, None).is_err());
+        let (since, until) = range(Some("2026-01-01"), Some("2026-01-31"), None, None).unwrap();
+        assert!(until.unwrap() > since);
+    }
+
+    fn scope(values: &[(&'static str, &str)]) -> Scope {
+        Scope {
+            values: values.iter().map(|(k, v)| (*k, v.to_string())).collect(),
+            sections: Vec::new(),
+        }
+    }
+
+    fn top() -> Scope {
+        let mut bugs = scope(&[("group", "bug")]);
+        bugs.sections = vec![(
+            "items",
+            vec![
+                scope(&[("title", "Crash"), ("key", "S-1")]),
+                scope(&[("title", "{{heading}}"), ("key", "S-2")]),
+            ],
+        )];
+        let mut top = scope(&[("heading", "v1.0")]);
+        top.sections = vec![("groups", vec![bugs]), ("empty", Vec::new())];
+        top
+    }
+
+    #[test]
+    fn fill_nested_sections() {
+        let text = fill(
+            "{{heading}}:{{#groups}} {{group}}[{{#items}}{{ key }}={{title}}@{{heading}};{{/items}}]{{/groups}}",
+            &[&top()],
+        )
+        .unwrap();
+        assert_eq!(text, "v1.0: bug[S-1=Crash@v1.0;S-2={{heading}}@v1.0;]");
+        assert_eq!(fill("a{{#empty}}b{{/empty}}c", &[&top()]).unwrap(), "ac");
+    }
+
+    #[test]
+    fn fill_errors() {
+        for bad in [
+            "{{heading",
+            "{{#groups}}",
+            "{{/groups}}",
+            "{{#missing}}{{/missing}}",
+            "{{missing}}",
+            "{{#groups}}{{title}}{{/groups}}",
+        ] {
+            assert!(fill(bad, &[&top()]).is_err(), "{:}", bad);
+        }
+    }
+
+    #[test]
+    fn render_standalone_tags() {
+        let rows = Vec::new();
+        let since = Utc::now() - Duration::days(7);
+        let text = render(DEFAULT_TEMPLATE, "v1.0", since, None, &rows, GroupBy::Label).unwrap();
+        assert_eq!(text, "## v1.0\n");
+        let text = render(
+            "{{count}} items\n  {{#items}}  \n- {{key}}\n{{/items}}\r\nend",
+            "",
+            since,
+            None,
+            &rows,
+            GroupBy::None,
+        )
+        .unwrap();
+        assert_eq!(text, "0 items\nend\n");
+    }
+}
@@ -0,0 +1,65 @@
+use colored::*;
+
+use colors_transform::Color;
+use colors_transform::Rgb;
+
+/// Kitemaker's named label colors
+const COLOR_MAPPING: [(&str, &str); 31] = [
+    ("gray", "#8D8D8D"),
+    ("mauve", "#8E8C99"),
+    ("slate", "#8B8D98"),
+    ("sage", "#B8BCBA"),
+    ("olive", "#898E87"),
+    ("sand", "#8D8D86"),
+    ("tomato", "#E54D2E"),
+    ("red", "#E5484D"),
+    ("ruby", "#E54666"),
+    ("crimson", "#E93D82"),
+    ("pink", "#D6409F"),
+    ("plum", "#AB4ABA"),
+    ("purple", "#8E4EC6"),
+    ("violet", "#6E56CF"),
+    ("iris", "#5B5BD6"),
+    ("indigo", "#3E63DD"),
+    ("blue", "#0090FF"),
+    ("cyan", "#00A2C7"),
+    ("teal", "#12A594"),
+    ("jade", "#29A383"),
+    ("green", "#30A46C"),
+    ("grass", "#46A758"),
+    ("bronze", "#A18072"),
+    ("gold", "#978365"),
+    ("brown", "#AD7F58"),
+    ("orange", "#F76B15"),
+    ("amber", "#FFC53D"),
+    ("yellow", "#FFE629"),
+    ("lime", "#BDEE63"),
+    ("mint", "#86EAD4"),
+    ("sky", "#7CE2FE"),
+];
+
+/// RGB values for a label color, which is either a hex string or one of the named colors
+pub fn label_rgb(color: &str) -> (u8, u8, u8) {
+    let hex_color = if color.starts_with('#') {
+        color
+    } else {
+        COLOR_MAPPING
+            .iter()
+            .find(|(name, _)| *name == color)
+            .map_or("#FFFFFF", |(_, hex)| *hex)
+    };
+    match Rgb::from_hex_str(hex_color) {
+        Ok(rgb) => (
+            rgb.get_red() as u8,
+            rgb.get_green() as u8,
+            rgb.get_blue() as u8,
+        ),
+        Err(_) => (255, 255, 255),
+    }
+}
+
+/// A label name tinted with its color
+pub fn label(name: &str, color: &str) -> ColoredString {
+    let (r, g, b) = label_rgb(color);
+    name.truecolor(r, g, b)
+}
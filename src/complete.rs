@@ -0,0 +1,273 @@
+//! Shell completion: `km completions` prints a script for bash, zsh or fish, which asks
+//! the hidden `km __complete` for space keys, work item numbers, statuses, labels and
+//! usernames and falls back to clap's static completion of commands and flags
+//!
+//! Values come from the cache only, as completion has to be instant and work offline:
+//! the organization is cached whenever it's fetched, and work item numbers whenever
+//! `km item list` or `km search` sees them.
+
+use anyhow::{anyhow, Result};
+
+use structopt::clap::{App, Shell};
+
+use std::collections::BTreeSet;
+
+use crate::cache;
+use crate::resolve::{Organization, Space};
+use crate::search;
+
+const BASH: &str = r#"
+_km_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" values
+    mapfile -t values < <(km __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null)
+    if [[ ${#values[@]} -eq 0 ]]; then
+        _km "$@"
+    elif [[ $cur == [\"\']* ]]; then
+        COMPREPLY=("${values[@]}")
+    else
+        mapfile -t COMPREPLY < <(printf '%q\n' "${values[@]}")
+    fi
+}
+
+complete -F _km_dynamic -o bashdefault -o default km
+"#;
+
+const ZSH: &str = r#"
+_km_dynamic() {
+    local -a values
+    values=("${(@f)$(km __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)}")
+    if [[ -n "${values[1]}" ]]; then
+        compadd -a values
+    else
+        _km "$@"
+    fi
+}
+
+compdef _km_dynamic km
+"#;
+
+const FISH: &str = r#"
+function __km_complete
+    km __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null
+end
+
+complete -c km -a '(__km_complete)'
+"#;
+
+/// The completion script for `shell`
+pub fn script(shell: &str, mut app: App) -> Result<String> {
+    let (target, dynamic) = match shell {
+        "bash" => (Shell::Bash, BASH),
+        "zsh" => (Shell::Zsh, ZSH),
+        "fish" => (Shell::Fish, FISH),
+        _ => return Err(anyhow!("Unknown shell {:} (bash, zsh or fish)", shell)),
+    };
+    let mut generated = Vec::new();
+    app.gen_completions_to("km", target, &mut generated);
+    let mut text = String::from_utf8(generated)?;
+
+    // zsh's script calls itself at the end, as if it were autoloaded; it is sourced instead
+    if shell == "zsh" {
+        text = text.trim_end().trim_end_matches("_km \"$@\"").to_string();
+    }
+    Ok(text + dynamic)
+}
+
+fn items_cache(space_key: &str) -> String {
+    format!("items-{:}", space_key)
+}
+
+/// Remember work item numbers seen in a space, for completion
+pub fn remember_items<I: IntoIterator<Item = String>>(space_key: &str, numbers: I) {
+    let name = items_cache(space_key);
+    let mut known: BTreeSet<u64> = cache::load(&name, None).unwrap_or_default();
+    let before = known.len();
+    known.extend(numbers.into_iter().filter_map(|n| n.parse::<u64>().ok()));
+    if known.len() != before {
+        let _ = cache::store(&name, &known);
+    }
+}
+
+/// Work item numbers seen in a space by `km item list` or `km search`
+pub fn known_numbers(space_key: &str) -> BTreeSet<u64> {
+    let mut numbers: BTreeSet<u64> = cache::load(&items_cache(space_key), None).unwrap_or_default();
+    numbers.extend(
+        search::cached_numbers(space_key)
+            .iter()
+            .filter_map(|n| n.parse::<u64>().ok()),
+    );
+    numbers
+}
+
+/// Whether `word` names an option that takes a value in one of `apps`, the command and
+/// the subcommands given so far, so the next word is its value rather than an argument
+fn takes_value(apps: &[&App], word: &str) -> bool {
+    let (long, short) = match word.strip_prefix("--") {
+        Some(long) => (Some(long), None),
+        None => (None, word.strip_prefix('-').and_then(|s| s.chars().next())),
+    };
+    // `--name=value` and `-nvalue` carry their value with them
+    if word.contains('=') || (short.is_some() && word.chars().count() > 2) {
+        return false;
+    }
+    apps.iter().any(|app| {
+        app.p.opts.iter().any(|o| {
+            (long.is_some() && o.s.long == long) || (short.is_some() && o.s.short == short)
+        })
+    })
+}
+
+enum Kind {
+    Spaces,
+    Numbers,
+    Statuses,
+    Labels,
+    Users,
+}
+
+struct Context<'a> {
+    org: &'a Organization,
+    /// Space keys of the work item numbers already on the command line
+    keys: Vec<String>,
+}
+
+/// The space key of a work item number, if it names a space
+fn key_of(org: &Organization, word: &str) -> Option<String> {
+    let (key, _) = word.rsplit_once('-')?;
+    org.spaces
+        .iter()
+        .find(|s| s.key.eq_ignore_ascii_case(key))
+        .map(|s| s.key.clone())
+}
+
+impl Context<'_> {
+    /// Spaces to take statuses and labels from: those of the numbers given, or all
+    fn spaces(&self) -> impl Iterator<Item = &Space> + '_ {
+        self.org
+            .spaces
+            .iter()
+            .filter(move |s| self.keys.is_empty() || self.keys.contains(&s.key))
+    }
+
+    fn values(&self, kind: &Kind) -> Vec<String> {
+        let mut values: Vec<String> = match kind {
+            Kind::Spaces => self.org.spaces.iter().map(|s| s.key.clone()).collect(),
+            Kind::Numbers => self
+                .org
+                .spaces
+                .iter()
+                .flat_map(|s| {
+                    known_numbers(&s.key)
+                        .into_iter()
+                        .map(move |n| format!("{:}-{:}", s.key, n))
+                })
+                .collect(),
+            Kind::Statuses => self
+                .spaces()
+                .flat_map(|s| s.statuses.iter().map(|st| st.name.clone()))
+                .collect(),
+            Kind::Labels => self
+                .spaces()
+                .flat_map(|s| s.labels.iter().map(|l| l.name.clone()))
+                .collect(),
+            Kind::Users => std::iter::once("me".to_string())
+                .chain(
+                    self.org
+                        .users
+                        .iter()
+                        .filter(|u| !u.deactivated)
+                        .map(|u| u.username.clone()),
+                )
+                .collect(),
+        };
+        let mut seen = BTreeSet::new();
+        values.retain(|v| seen.insert(v.clone()));
+        values
+    }
+}
+
+/// Completions for the last of `words`, the command line after `km`, whose commands and
+/// options `app` describes
+pub fn candidates(words: &[String], app: &App) -> Vec<String> {
+    let org: Organization = match cache::load(cache::ORGANIZATION, None) {
+        Some(org) => org,
+        None => return Vec::new(),
+    };
+    let (current, before) = match words.split_last() {
+        Some((current, before)) => (current.trim_start_matches(['"', '\'']), before),
+        None => return Vec::new(),
+    };
+    if current.starts_with('-') {
+        return Vec::new();
+    }
+
+    // Subcommands and arguments, leaving out options and their values
+    let mut apps = vec![app];
+    let mut command: Vec<&str> = Vec::new();
+    let mut args: Vec<&str> = Vec::new();
+    let mut words = before.iter().map(String::as_str);
+    while let Some(word) = words.next() {
+        if word.starts_with('-') {
+            if takes_value(&apps, word) {
+                words.next();
+            }
+            continue;
+        }
+        // Subcommands come before any arguments
+        let current = apps[apps.len() - 1];
+        let subcommand = match args.is_empty() {
+            true => current.p.subcommands.iter().find(|s| s.get_name() == word),
+            false => None,
+        };
+        match subcommand {
+            Some(subcommand) => {
+                apps.push(subcommand);
+                command.push(word);
+            }
+            None => args.push(word),
+        }
+    }
+    if command.is_empty() {
+        return Vec::new();
+    }
+
+    let context = Context {
+        org: &org,
+        keys: args.iter().filter_map(|a| key_of(&org, a)).collect(),
+    };
+
+    let previous = before.last().map(String::as_str).unwrap_or("");
+    let kinds: Vec<Kind> = match (previous, command.as_slice()) {
+        ("--status", _) => vec![Kind::Statuses],
+        ("--label", _) | ("-l", ["item", "create"]) => vec![Kind::Labels],
+        ("-m" | "--member" | "-w" | "--watcher", _) => vec![Kind::Users],
+        ("--space", _) | ("-s", ["search"] | ["item", "import"]) => vec![Kind::Spaces],
+        ("--before" | "--after", _) => vec![Kind::Numbers],
+        (option, _) if takes_value(&apps, option) => Vec::new(),
+        (_, ["board"] | ["item", "list" | "matrix" | "create"]) if args.is_empty() => {
+            vec![Kind::Spaces]
+        }
+        (_, ["item", "view" | "edit" | "rank" | "comment"]) => vec![Kind::Numbers],
+        (_, ["item", action @ ("move" | "assign" | "label")]) => {
+            let value = match *action {
+                "move" => Kind::Statuses,
+                "assign" => Kind::Users,
+                _ => Kind::Labels,
+            };
+            // After a number, the next word may be another number or the value
+            match (args.is_empty(), current.is_empty()) {
+                (true, _) => vec![Kind::Numbers],
+                (false, true) => vec![value],
+                (false, false) => vec![Kind::Numbers, value],
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    let current = current.to_lowercase();
+    kinds
+        .iter()
+        .flat_map(|kind| context.values(kind))
+        .filter(|v| v.to_lowercase().starts_with(&current))
+        .collect()
+}
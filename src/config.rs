@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Context, Result};
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use toml_edit::DocumentMut;
+
+/// User configuration, read from `$KM_CONFIG` or `<config dir>/km/config.toml`
+#[derive(Deserialize, Default, Debug)]
+pub struct Config {
+    /// Your Kitemaker username, used wherever `me` is accepted
+    pub username: Option<String>,
+
+    /// GraphQL endpoint to use instead of Kitemaker's, also set with `$KM_ENDPOINT`
+    pub endpoint: Option<String>,
+
+    /// Seconds to wait for a response before giving up (default 30), also set with
+    /// `$KM_TIMEOUT`
+    pub timeout_seconds: Option<u64>,
+
+    /// Times a query is sent again after a timeout, 429 or gateway error (default 3), also
+    /// set with `$KM_RETRIES`
+    pub retries: Option<u32>,
+
+    /// Retry mutations too, at the risk of applying them twice
+    pub retry_mutations: Option<bool>,
+
+    /// Proxy for all requests, e.g. `http://proxy.example.com:3128`, also set with
+    /// `$KM_PROXY`. Without it, the usual `$HTTPS_PROXY` and `$NO_PROXY` are used.
+    pub proxy: Option<String>,
+
+    /// Comma-separated hosts, domains and IP ranges to reach without the proxy, also set
+    /// with `$KM_NO_PROXY`. Applies to `proxy` or, without it, the one from the environment
+    /// in addition to `$NO_PROXY`.
+    pub no_proxy: Option<String>,
+
+    /// PEM files with certificate authorities to trust besides the system's, also set with
+    /// `$KM_CA_CERTS` (separated like `$PATH`)
+    #[serde(default)]
+    pub ca_certs: Vec<String>,
+
+    /// Client certificate for mutual TLS, as PEM (with `client_key`) or PKCS#12, also set
+    /// with `$KM_CLIENT_CERT`
+    pub client_cert: Option<String>,
+
+    /// PEM file with the PKCS#8 private key of `client_cert`, also set with `$KM_CLIENT_KEY`
+    pub client_key: Option<String>,
+
+    /// Password of a PKCS#12 `client_cert`, also set with `$KM_CLIENT_CERT_PASSWORD`
+    pub client_cert_password: Option<String>,
+
+    /// User-Agent header to send instead of `km/VERSION`, also set with `$KM_USER_AGENT`
+    pub user_agent: Option<String>,
+
+    /// How long cached work items are used before fetching them again (default 15)
+    pub cache_minutes: Option<u64>,
+
+    /// Name of branches made by `km git branch`, from `{key}`, `{KEY}`, `{number}` and
+    /// `{title}` (default `{key}-{number}-{title}`, e.g. `abc-123-fix-login-timeout`)
+    pub branch_pattern: Option<String>,
+
+    /// Move work items to an in-progress status when `km git branch` makes their branch
+    pub start_on_branch: Option<bool>,
+
+    /// Saved `item list --filter` expressions by name
+    #[serde(default)]
+    pub filters: BTreeMap<String, String>,
+}
+
+/// The environment variable if it's set, the config value otherwise
+pub fn env_or(var: &str, value: Option<String>) -> Option<String> {
+    std::env::var(var).ok().filter(|v| !v.is_empty()).or(value)
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        match std::env::var_os("KM_CONFIG") {
+            Some(p) => PathBuf::from(p),
+            None => dirs::config_dir()
+                .unwrap_or_default()
+                .join("km")
+                .join("config.toml"),
+        }
+    }
+
+    /// Load the config file, falling back to defaults if it doesn't exist
+    pub fn load() -> Result<Config> {
+        let path = Config::path();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read config {:}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid config {:}", path.display()))
+    }
+
+    /// Save a filter in the `[filters]` table of the config file, keeping the rest of the
+    /// file, comments and all, as it is
+    pub fn save_filter(&mut self, name: &str, text: &str) -> Result<()> {
+        let path = Config::path();
+        let existing = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Could not read config {:}", path.display()))?
+        } else {
+            String::new()
+        };
+        let mut document: DocumentMut = existing
+            .parse()
+            .with_context(|| format!("Invalid config {:}", path.display()))?;
+        document
+            .entry("filters")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or_else(|| anyhow!("filters in {:} is not a table", path.display()))?
+            .insert(name, toml_edit::value(text));
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, document.to_string())
+            .with_context(|| format!("Could not write config {:}", path.display()))?;
+        self.filters.insert(name.to_string(), text.to_string());
+        Ok(())
+    }
+}
@@ -0,0 +1,659 @@
+//! Filter expressions for `km item list --filter`.
+//!
+//! An expression is a list of terms that must all match. Terms can be combined with
+//! `or`, negated with `not` or a leading `-`, and grouped with parentheses:
+//!
+//! ```text
+//! type:in-progress label:bug member:me initiative:none
+//! (label:bug or label:crash) -status:Blocked updated:7d
+//! created>=2026-09-01 closed:2026-09-01..2026-09-30 title~"^\[api\]"
+//! ```
+//!
+//! Supported terms:
+//!
+//! * `status:NAME`, `type:TYPE` - status name or status type (backlog, todo, in-progress, done, archived)
+//! * `label:NAME`, `member:USER`, `watcher:USER`, `initiative:NUMBER|TITLE` - `none` matches items without any
+//! * `effort:VALUE`, `impact:VALUE` - `none` matches items where it isn't set
+//! * `created`, `updated`, `closed` - compare with `>`, `>=`, `<`, `<=` against a date (`2026-09-01`,
+//!   `today`, `yesterday`) or a relative time (`3d`, `2w`, `12h`), or use `:FROM..TO`, `:DATE`,
+//!   `:7d` (within the last 7 days) or `:none`
+//! * `title:TEXT` substring, `title~REGEX` regular expression; bare words also match the title
+//! * `@NAME` - a saved filter from the config
+//!
+//! Status type terms are pushed to the server as a `StatusFilter`, everything else is
+//! evaluated on the fetched items.
+
+use anyhow::{anyhow, bail, Result};
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+
+use regex::Regex;
+
+use std::str::FromStr;
+
+use crate::api::{items_query::StatusType, WorkItem};
+use crate::config::Config;
+
+pub fn all_status_types() -> Vec<StatusType> {
+    vec![
+        StatusType::BACKLOG,
+        StatusType::TODO,
+        StatusType::IN_PROGRESS,
+        StatusType::DONE,
+        StatusType::ARCHIVED,
+    ]
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateField {
+    Created,
+    Updated,
+    Closed,
+}
+
+/// Half open `[from, to)` range
+#[derive(Debug)]
+struct DateRange {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    fn contains(&self, t: &DateTime<Utc>) -> bool {
+        self.from.is_none_or(|f| t >= &f) && self.to.is_none_or(|to| t < &to)
+    }
+}
+
+#[derive(Debug)]
+enum Term {
+    Status(String),
+    StatusType(StatusType),
+    Label(Option<String>),
+    Member(Option<String>),
+    Watcher(Option<String>),
+    Effort(Option<String>),
+    Impact(Option<String>),
+    Initiative(Option<String>),
+    /// `None` matches items where the date isn't set
+    Date(DateField, Option<DateRange>),
+    Title(String),
+    TitleRegex(Regex),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(text: &str, config: &Config) -> Result<Filter> {
+        let expr = Parser::new(text, config, Vec::new())?.parse()?;
+        Ok(Filter { expr })
+    }
+
+    pub fn matches(&self, item: &WorkItem) -> bool {
+        self.expr.matches(item)
+    }
+
+    /// The status types an item could have and still match this filter
+    pub fn status_types(&self) -> Vec<StatusType> {
+        all_status_types()
+            .into_iter()
+            .filter(|t| self.expr.matches_status_type(t) != Some(false))
+            .collect()
+    }
+}
+
+fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+fn matches_option(value: &Option<String>, wanted: &Option<String>) -> bool {
+    match (value, wanted) {
+        (None, None) => true,
+        (Some(v), Some(w)) => eq_ignore_case(v, w),
+        _ => false,
+    }
+}
+
+/// `None` matches an empty list, otherwise any entry must match
+fn matches_any<'a>(mut names: impl Iterator<Item = &'a str>, wanted: &Option<String>) -> bool {
+    match wanted {
+        None => names.next().is_none(),
+        Some(w) => names.any(|n| eq_ignore_case(n, w)),
+    }
+}
+
+impl Expr {
+    fn matches(&self, item: &WorkItem) -> bool {
+        match self {
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(item)),
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(item)),
+            Expr::Not(e) => !e.matches(item),
+            Expr::Term(term) => term.matches(item),
+        }
+    }
+
+    /// Three valued evaluation knowing only the status type. `None` means it depends
+    /// on other fields.
+    fn matches_status_type(&self, status_type: &StatusType) -> Option<bool> {
+        match self {
+            Expr::And(exprs) => {
+                let results: Vec<Option<bool>> = exprs
+                    .iter()
+                    .map(|e| e.matches_status_type(status_type))
+                    .collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.iter().all(|r| *r == Some(true)) {
+                    Some(true)
+                } else {
+                    None
+                }
+            }
+            Expr::Or(exprs) => {
+                let results: Vec<Option<bool>> = exprs
+                    .iter()
+                    .map(|e| e.matches_status_type(status_type))
+                    .collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.iter().all(|r| *r == Some(false)) {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Expr::Not(e) => e.matches_status_type(status_type).map(|r| !r),
+            Expr::Term(Term::StatusType(t)) => Some(t == status_type),
+            Expr::Term(_) => None,
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, item: &WorkItem) -> bool {
+        match self {
+            Term::Status(name) => eq_ignore_case(&item.status.name, name),
+            Term::StatusType(t) => &item.status.type_ == t,
+            Term::Label(name) => matches_any(item.labels.iter().map(|l| l.name.as_str()), name),
+            Term::Member(name) => {
+                matches_any(item.members.iter().map(|m| m.username.as_str()), name)
+            }
+            Term::Watcher(name) => {
+                matches_any(item.watchers.iter().map(|w| w.username.as_str()), name)
+            }
+            Term::Effort(value) => matches_option(&item.effort, value),
+            Term::Impact(value) => matches_option(&item.impact, value),
+            Term::Initiative(wanted) => match wanted {
+                None => item.initiatives.is_empty(),
+                Some(w) => item
+                    .initiatives
+                    .iter()
+                    .any(|i| eq_ignore_case(&i.number, w) || eq_ignore_case(&i.title, w)),
+            },
+            Term::Date(field, range) => {
+                let value = match field {
+                    DateField::Created => Some(&item.created_at),
+                    DateField::Updated => Some(&item.updated_at),
+                    DateField::Closed => item.closed_at.as_ref(),
+                };
+                match (value, range) {
+                    (None, None) => true,
+                    (Some(d), Some(r)) => r.contains(&d.0),
+                    _ => false,
+                }
+            }
+            Term::Title(text) => item.title.to_lowercase().contains(&text.to_lowercase()),
+            Term::TitleRegex(re) => re.is_match(&item.title),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word { text: String, quoted: bool },
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::Open);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::Close);
+        } else {
+            let mut word = String::new();
+            let mut quoted = false;
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '(' || c == ')' {
+                    break;
+                }
+                chars.next();
+                if c == '"' {
+                    // Only a fully quoted word is plain text, `title:"a b"` is still a term
+                    quoted = quoted || word.is_empty();
+                    loop {
+                        match chars.next() {
+                            None => bail!("Unterminated quote in filter"),
+                            Some('"') => break,
+                            Some('\\') if chars.peek() == Some(&'"') => {
+                                word.push('"');
+                                chars.next();
+                            }
+                            Some(c) => word.push(c),
+                        }
+                    }
+                } else {
+                    word.push(c);
+                }
+            }
+            tokens.push(Token::Word { text: word, quoted });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    config: &'a Config,
+    /// Saved filters being expanded, to catch cycles
+    expanding: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &str, config: &'a Config, expanding: Vec<String>) -> Result<Parser<'a>> {
+        Ok(Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            config,
+            expanding,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr> {
+        let expr = self.parse_or()?;
+        if self.pos < self.tokens.len() {
+            bail!("Unexpected ')' in filter");
+        }
+        Ok(expr)
+    }
+
+    fn keyword(&self) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word {
+                text,
+                quoted: false,
+            }) => Some(text.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut exprs = vec![self.parse_and()?];
+        while self.keyword().as_deref() == Some("or") {
+            self.pos += 1;
+            exprs.push(self.parse_and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut exprs = Vec::new();
+        loop {
+            match self.tokens.get(self.pos) {
+                None | Some(Token::Close) => break,
+                _ => {}
+            }
+            match self.keyword().as_deref() {
+                Some("or") => break,
+                Some("and") => {
+                    self.pos += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            exprs.push(self.parse_unary()?);
+        }
+        match exprs.len() {
+            0 => bail!("Expected a filter term"),
+            1 => Ok(exprs.remove(0)),
+            _ => Ok(Expr::And(exprs)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.keyword().as_deref() == Some("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+
+        match self.tokens.get(self.pos) {
+            Some(Token::Open) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                if self.tokens.get(self.pos) != Some(&Token::Close) {
+                    bail!("Missing ')' in filter");
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(Token::Word { text, quoted }) => {
+                let (text, quoted) = (text.clone(), *quoted);
+                self.pos += 1;
+                if quoted {
+                    return Ok(Expr::Term(Term::Title(text)));
+                }
+                match text.strip_prefix('-') {
+                    Some(rest) if !rest.is_empty() => {
+                        Ok(Expr::Not(Box::new(self.parse_word(rest)?)))
+                    }
+                    _ => self.parse_word(&text),
+                }
+            }
+            _ => bail!("Expected a filter term"),
+        }
+    }
+
+    fn parse_word(&self, word: &str) -> Result<Expr> {
+        if let Some(name) = word.strip_prefix('@') {
+            if self.expanding.iter().any(|n| n == name) {
+                bail!("Saved filter @{:} refers to itself", name);
+            }
+            let text = self
+                .config
+                .filters
+                .get(name)
+                .ok_or_else(|| anyhow!("No saved filter named {:}", name))?;
+            let mut expanding = self.expanding.clone();
+            expanding.push(name.to_string());
+            return Parser::new(text, self.config, expanding)?.parse();
+        }
+
+        let op_pos = match word.find([':', '~', '<', '>']) {
+            None => return Ok(Expr::Term(Term::Title(word.to_string()))),
+            Some(p) => p,
+        };
+        let field = word[..op_pos].to_lowercase();
+        let rest = &word[op_pos..];
+        let (op, value) = [">=", "<=", ":", "~", ">", "<"]
+            .iter()
+            .find_map(|op| rest.strip_prefix(op).map(|v| (*op, v)))
+            .expect("operator");
+
+        let optional = |v: &str| -> Option<String> {
+            if v.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(v.to_string())
+            }
+        };
+
+        let term = match (field.as_str(), op) {
+            ("status", ":") => Term::Status(value.to_string()),
+            ("type", ":") | ("status-type", ":") => {
+                Term::StatusType(StatusType::from_str(value).map_err(|e| anyhow!(e))?)
+            }
+            ("label", ":") => Term::Label(optional(value)),
+            ("member", ":") | ("assignee", ":") => Term::Member(self.user(value)?),
+            ("watcher", ":") => Term::Watcher(self.user(value)?),
+            ("effort", ":") => Term::Effort(optional(value)),
+            ("impact", ":") => Term::Impact(optional(value)),
+            ("initiative", ":") => Term::Initiative(optional(value)),
+            ("title", ":") => Term::Title(value.to_string()),
+            ("title", "~") => {
+                let pattern = value
+                    .strip_prefix('/')
+                    .and_then(|v| v.strip_suffix('/'))
+                    .unwrap_or(value);
+                Term::TitleRegex(Regex::new(pattern)?)
+            }
+            ("created", _) | ("updated", _) | ("closed", _) if op != "~" => {
+                let field = match field.as_str() {
+                    "created" => DateField::Created,
+                    "updated" => DateField::Updated,
+                    _ => DateField::Closed,
+                };
+                Term::Date(field, parse_date_range(op, value)?)
+            }
+            _ => bail!("Unknown filter term {:}", word),
+        };
+        Ok(Expr::Term(term))
+    }
+
+    /// Resolve `me` to the configured username
+    fn user(&self, value: &str) -> Result<Option<String>> {
+        if value.eq_ignore_ascii_case("me") {
+            return match &self.config.username {
+                Some(username) => Ok(Some(username.clone())),
+                None => bail!(
+                    "Set username in {:} to use 'me' in filters",
+                    Config::path().display()
+                ),
+            };
+        }
+        if value.eq_ignore_ascii_case("none") {
+            Ok(None)
+        } else {
+            Ok(Some(value.to_string()))
+        }
+    }
+}
+
+/// A point in time as the `[lower, upper)` span it covers; calendar days cover the whole
+/// day, relative times are a single instant
+pub fn parse_date(value: &str) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let start_of = |date: NaiveDate| -> Result<DateTime<Utc>> {
+        Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight"))
+            .earliest()
+            .map(|d| d.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("Invalid date {:}", value))
+    };
+    let day = |date: NaiveDate| -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let next = date
+            .succ_opt()
+            .ok_or_else(|| anyhow!("Invalid date {:}", value))?;
+        Ok((start_of(date)?, start_of(next)?))
+    };
+
+    let today = Local::now().date_naive();
+    match value.to_lowercase().as_str() {
+        "today" => return day(today),
+        "yesterday" => return day(today - Duration::days(1)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return day(date);
+    }
+
+    let invalid = || anyhow!("Invalid date {:} (expected YYYY-MM-DD or e.g. 7d)", value);
+    let (amount, unit) = match value.char_indices().last() {
+        Some((i, unit)) => (&value[..i], unit),
+        None => return Err(invalid()),
+    };
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let ago = match unit {
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => return Err(invalid()),
+    };
+    let t = ago
+        .and_then(|ago| Utc::now().checked_sub_signed(ago))
+        .ok_or_else(invalid)?;
+    Ok((t, t))
+}
+
+fn parse_date_range(op: &str, value: &str) -> Result<Option<DateRange>> {
+    let range = match op {
+        ">=" => DateRange {
+            from: Some(parse_date(value)?.0),
+            to: None,
+        },
+        ">" => DateRange {
+            from: Some(parse_date(value)?.1),
+            to: None,
+        },
+        "<" => DateRange {
+            from: None,
+            to: Some(parse_date(value)?.0),
+        },
+        "<=" => DateRange {
+            from: None,
+            to: Some(parse_date(value)?.1),
+        },
+        _ if value.eq_ignore_ascii_case("none") => return Ok(None),
+        _ => match value.split_once("..") {
+            Some((from, to)) => DateRange {
+                from: if from.is_empty() {
+                    None
+                } else {
+                    Some(parse_date(from)?.0)
+                },
+                to: if to.is_empty() {
+                    None
+                } else {
+                    Some(parse_date(to)?.1)
+                },
+            },
+            None => {
+                let (lower, upper) = parse_date(value)?;
+                DateRange {
+                    from: Some(lower),
+                    // A relative time on its own means "since then"
+                    to: if lower == upper { None } else { Some(upper) },
+                }
+            }
+        },
+    };
+    Ok(Some(range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item() -> WorkItem {
+        serde_json::from_value(serde_json::json!({
+            "id": "w1",
+            "number": "12",
+            "title": "[api] Login times out",
+            "sort": "a",
+            "effort": "M",
+            "impact": null,
+            "createdAt": "2026-09-10T12:00:00Z",
+            "updatedAt": Utc::now().timestamp_millis(),
+            "closedAt": null,
+            "status": { "id": "s", "name": "Blocked", "type": "IN_PROGRESS" },
+            "labels": [{ "id": "l", "name": "bug", "color": "red" }],
+            "members": [{ "id": "u", "username": "alex" }],
+            "watchers": [],
+            "initiatives": [{ "id": "i", "number": "3", "title": "Auth" }]
+        }))
+        .unwrap()
+    }
+
+    fn config() -> Config {
+        let mut config = Config {
+            username: Some("alex".to_string()),
+            ..Config::default()
+        };
+        config
+            .filters
+            .insert("mine".to_string(), "member:me".to_string());
+        config.filters.insert("a".to_string(), "@b".to_string());
+        config
+            .filters
+            .insert("b".to_string(), "label:bug @a".to_string());
+        config
+    }
+
+    fn matches(text: &str) -> bool {
+        Filter::parse(text, &config()).unwrap().matches(&item())
+    }
+
+    #[test]
+    fn terms() {
+        assert!(matches(
+            "type:in-progress label:BUG member:me initiative:auth"
+        ));
+        assert!(matches("status:blocked effort:m impact:none watcher:none"));
+        assert!(matches("login title:times title~^\\[api\\]"));
+        assert!(matches("\"times out\""));
+        assert!(!matches("label:none"));
+        assert!(!matches("closed:7d"));
+        assert!(matches(
+            "closed:none created>=2026-09-01 created<2026-10-01 updated:1d"
+        ));
+    }
+
+    #[test]
+    fn boolean_operators() {
+        assert!(matches("label:crash or label:bug"));
+        assert!(matches("(label:crash or label:bug) -status:done"));
+        assert!(!matches("not (label:bug and member:alex)"));
+        assert!(matches("label:bug and not label:crash"));
+        assert!(matches("@mine"));
+    }
+
+    #[test]
+    fn status_types() {
+        let types = |text: &str| Filter::parse(text, &config()).unwrap().status_types();
+        assert_eq!(types("type:done or type:archived").len(), 2);
+        assert_eq!(types("-type:backlog label:bug").len(), 4);
+        assert_eq!(types("type:todo or label:bug").len(), 5);
+        assert!(types("type:todo type:done").is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |text: &str| Filter::parse(text, &config());
+        assert!(parse("").is_err());
+        assert!(parse("(label:bug").is_err());
+        assert!(parse("label:bug)").is_err());
+        assert!(parse("title:\"open").is_err());
+        assert!(parse("color:red").is_err());
+        assert!(parse("type:started").is_err());
+        assert!(parse("@missing").is_err());
+        assert!(parse("@a").is_err());
+        assert!(parse("created>3é").is_err());
+        assert!(parse("updated:99999999999999d").is_err());
+        assert!(parse("member:me").is_ok());
+        assert!(Filter::parse("member:me", &Config::default()).is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let (from, to) = parse_date("2026-09-10").unwrap();
+        assert_eq!(to - from, Duration::days(1));
+        let (from, to) = parse_date("3d").unwrap();
+        assert_eq!(from, to);
+        assert!(from < Utc::now() - Duration::days(2));
+        assert!(parse_date("3x").is_err());
+        assert!(parse_date("d").is_err());
+        assert!(parse_date("9999999999999w").is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Recording API responses to a directory (`--record`) and serving them back without the
+//! network (`--replay`), for reproducible runs and tests
+//!
+//! Every request is saved as `OPERATION-HASH.json`, where the hash covers the variables
+//! (and the query text, for queries without an operation name). The same request sent
+//! again in a session gets `-2`, `-3` and so on, so a replay sees the responses in the
+//! order they were recorded; after the last one, it keeps getting the last one.
+
+use anyhow::{anyhow, Context, Result};
+
+use reqwest::StatusCode;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Fixture {
+    operation: String,
+    variables: Value,
+    status: u16,
+    /// The response body, as JSON if it is JSON
+    response: Value,
+}
+
+pub struct Fixtures {
+    mode: Mode,
+    /// Times each request was seen so far
+    seen: Mutex<HashMap<String, usize>>,
+}
+
+/// Serialize with object keys sorted, so equal variables always give the same text
+fn canonical(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = entries
+                .iter()
+                .map(|(k, v)| format!("{:}:{:}", Value::String(k.to_string()), canonical(v)))
+                .collect();
+            format!("{{{:}}}", fields.join(","))
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical).collect();
+            format!("[{:}]", values.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// 64-bit FNV-1a, which unlike the standard library's hashers is the same everywhere
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn key(name: &str, body: &Value) -> String {
+    let mut text = canonical(&body["variables"]);
+    if body.get("operationName").is_none_or(Value::is_null) {
+        text.push_str(body["query"].as_str().unwrap_or_default());
+    }
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{:}-{:016x}", name, fnv1a(&text))
+}
+
+impl Fixtures {
+    pub fn new(mode: Mode) -> Result<Fixtures> {
+        match &mode {
+            Mode::Record(dir) => fs::create_dir_all(dir)
+                .with_context(|| format!("Could not create {:}", dir.display()))?,
+            Mode::Replay(dir) if !dir.is_dir() => {
+                return Err(anyhow!("No recordings to replay in {:}", dir.display()))
+            }
+            Mode::Replay(_) => {}
+        }
+        Ok(Fixtures {
+            mode,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The file for the `n`th sending of a request
+    fn file_name(&self, key: &str, n: usize) -> String {
+        if n == 1 {
+            format!("{:}.json", key)
+        } else {
+            format!("{:}-{:}.json", key, n)
+        }
+    }
+
+    /// Count another sending of a request
+    fn next(&self, key: &str) -> usize {
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let n = seen.entry(key.to_string()).or_insert(0);
+        *n += 1;
+        *n
+    }
+
+    /// The recorded response to the request when replaying, `None` when recording
+    pub fn replay(&self, name: &str, body: &Value) -> Result<Option<(StatusCode, String)>> {
+        let dir = match &self.mode {
+            Mode::Replay(dir) => dir,
+            Mode::Record(_) => return Ok(None),
+        };
+        let key = key(name, body);
+        let n = self.next(&key);
+        let path = (1..=n)
+            .rev()
+            .map(|i| dir.join(self.file_name(&key, i)))
+            .find(|p| p.exists())
+            .ok_or_else(|| {
+                anyhow!(
+                    "No recorded response for {:} with variables {:} in {:} (looked for {:})",
+                    name,
+                    body["variables"],
+                    dir.display(),
+                    self.file_name(&key, 1)
+                )
+            })?;
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Could not read {:}", path.display()))?;
+        let fixture: Fixture = serde_json::from_str(&text)
+            .with_context(|| format!("Invalid recording {:}", path.display()))?;
+        let status = StatusCode::from_u16(fixture.status)
+            .with_context(|| format!("Invalid status in {:}", path.display()))?;
+        let response = match fixture.response {
+            Value::String(text) => text,
+            other => other.to_string(),
+        };
+        Ok(Some((status, response)))
+    }
+
+    /// Save the response to the request when recording
+    pub fn record(&self, name: &str, body: &Value, status: StatusCode, text: &str) -> Result<()> {
+        let dir = match &self.mode {
+            Mode::Record(dir) => dir,
+            Mode::Replay(_) => return Ok(()),
+        };
+        let key = key(name, body);
+        let path = dir.join(self.file_name(&key, self.next(&key)));
+        let fixture = Fixture {
+            operation: name.to_string(),
+            variables: body["variables"].clone(),
+            status: status.as_u16(),
+            response: serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.into())),
+        };
+        fs::write(&path, serde_json::to_string_pretty(&fixture)? + "\n")
+            .with_context(|| format!("Could not write {:}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn keys_ignore_variable_order() {
+        let a = json!({"operationName": "Q", "variables": {"a": 1, "b": [{"x": 1, "y": 2}]}});
+        let b = json!({"operationName": "Q", "variables": {"b": [{"y": 2, "x": 1}], "a": 1}});
+        let c = json!({"operationName": "Q", "variables": {"a": 2, "b": []}});
+        assert_eq!(key("Q", &a), key("Q", &b));
+        assert_ne!(key("Q", &a), key("Q", &c));
+        assert!(key("Some Query", &a).starts_with("Some_Query-"));
+    }
+
+    #[test]
+    fn record_then_replay() {
+        let dir = std::env::temp_dir().join(format!("km-fixtures-{:}", std::process::id()));
+        let body = json!({"operationName": "Q", "variables": {"id": "1"}});
+        let other = json!({"operationName": "Q", "variables": {"id": "2"}});
+
+        let recorder = Fixtures::new(Mode::Record(dir.clone())).unwrap();
+        assert!(recorder.replay("Q", &body).unwrap().is_none());
+        recorder
+            .record("Q", &body, StatusCode::OK, "{\"data\":1}")
+            .unwrap();
+        recorder
+            .record("Q", &body, StatusCode::BAD_GATEWAY, "busy")
+            .unwrap();
+
+        let player = Fixtures::new(Mode::Replay(dir.clone())).unwrap();
+        let replay = |body: &Value| player.replay("Q", body).unwrap();
+        assert_eq!(
+            replay(&body),
+            Some((StatusCode::OK, "{\"data\":1}".to_string()))
+        );
+        assert_eq!(
+            replay(&body),
+            Some((StatusCode::BAD_GATEWAY, "busy".to_string()))
+        );
+        // After the last recording, it's given again
+        assert_eq!(
+            replay(&body),
+            Some((StatusCode::BAD_GATEWAY, "busy".to_string()))
+        );
+        assert!(player.replay("Q", &other).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(Fixtures::new(Mode::Replay(dir)).is_err());
+    }
+}
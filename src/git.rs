@@ -0,0 +1,280 @@
+//! Working with git for `km git`: branches named after work items, finding the work item
+//! a branch is about, and a commit-msg hook checking the work items commits reference.
+//! Also the dates of tags, for `km changelog`.
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use chrono::{DateTime, Utc};
+
+use colored::*;
+
+use regex::Regex;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::api::item_by_number_query::StatusType;
+use crate::api::{Api, QueryError};
+use crate::cache;
+use crate::complete;
+use crate::resolve::{self, Organization};
+
+/// Branch names unless configured otherwise: `{key}` and `{number}` make up the work item
+/// number, `{title}` is the title made into a slug
+pub const DEFAULT_BRANCH_PATTERN: &str = "{key}-{number}-{title}";
+
+/// Longest title slug in a branch name
+const MAX_SLUG: usize = 50;
+
+/// Lowercase words of the text joined by dashes, cut at a word boundary
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+    {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > MAX_SLUG {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(MAX_SLUG);
+    slug
+}
+
+/// The branch name for a work item, e.g. `abc-123-fix-login-timeout`
+pub fn branch_name(pattern: &str, key: &str, number: &str, title: &str) -> String {
+    pattern
+        .replace("{key}", &key.to_lowercase())
+        .replace("{KEY}", key)
+        .replace("{number}", number)
+        .replace("{title}", &slug(title))
+        .trim_matches(['-', '/', '_'])
+        .to_string()
+}
+
+/// The first work item number in the text whose key names a space, e.g. `ABC-123` in
+/// `feature/abc-123-fix-login`
+pub fn number_in(org: &Organization, text: &str) -> Option<String> {
+    let pattern = Regex::new(r"(?i)\b([a-z][a-z0-9]*)-(\d+)\b").unwrap();
+    let found = pattern.captures_iter(text).find_map(|c| {
+        org.spaces
+            .iter()
+            .find(|s| s.key.eq_ignore_ascii_case(&c[1]))
+            .map(|s| format!("{:}-{:}", s.key, &c[2]))
+    });
+    found
+}
+
+/// Run git and return its trimmed output
+pub fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .context("Could not run git")?;
+    if !output.status.success() {
+        bail!(
+            "git {:} failed: {:}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The checked out branch
+pub fn current_branch() -> Result<String> {
+    // Unlike `rev-parse`, this also works before the first commit
+    git(&["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .map_err(|_| anyhow!("Not on a branch (detached HEAD, or not a git repository)"))
+}
+
+/// Check out the branch, creating it from the current commit if it doesn't exist yet.
+/// Returns whether it was created.
+pub fn checkout(branch: &str) -> Result<bool> {
+    let exists = git(&[
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("refs/heads/{:}", branch),
+    ])
+    .is_ok();
+    if exists {
+        git(&["checkout", branch])?;
+    } else {
+        git(&["checkout", "-b", branch])?;
+    }
+    Ok(!exists)
+}
+
+/// When a tag was made: the tagger date of an annotated tag, the commit date otherwise
+pub fn tag_date(tag: &str) -> Result<DateTime<Utc>> {
+    let date = git(&[
+        "for-each-ref",
+        "--format=%(creatordate:iso-strict)",
+        &format!("refs/tags/{:}", tag),
+    ])?;
+    if date.is_empty() {
+        bail!("Could not find tag {:}", tag);
+    }
+    let date = DateTime::parse_from_rfc3339(&date)
+        .with_context(|| format!("Could not read the date of tag {:}", tag))?;
+    Ok(date.with_timezone(&Utc))
+}
+
+/// Marks hooks written by `install_hook`, so they can be replaced without --force
+const HOOK_MARK: &str = "# Installed by km git install-hook";
+
+/// Install a commit-msg hook that runs `km git check-message`. Returns where it went.
+pub fn install_hook(prepend: bool, force: bool) -> Result<PathBuf> {
+    // Respects core.hooksPath and works from worktrees
+    let path = PathBuf::from(git(&["rev-parse", "--git-path", "hooks/commit-msg"])?);
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.contains(HOOK_MARK) && !force {
+            bail!(
+                "{:} already exists, use --force to replace it",
+                path.display()
+            );
+        }
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Keep the hook quick when the API is unreachable; check-message then uses the cache
+    let hook = format!(
+        "#!/bin/sh\n{:}\nexec km --retries 0 --timeout 5 git check-message{:} \"$1\"\n",
+        HOOK_MARK,
+        if prepend { " --prepend" } else { "" }
+    );
+    fs::write(&path, hook).with_context(|| format!("Could not write {:}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
+
+/// The part of a commit message git keeps: no comments, nothing below the scissors line
+fn message_text(message: &str) -> String {
+    message
+        .lines()
+        .take_while(|l| !l.starts_with("# ------------------------ >8"))
+        .filter(|l| !l.starts_with('#'))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Every work item number in the text whose key names a space, without duplicates
+fn numbers_in(org: &Organization, text: &str) -> Vec<String> {
+    let mut numbers: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric() && c != '-') {
+        if let Some(number) = number_in(org, word) {
+            if !numbers.contains(&number) {
+                numbers.push(number);
+            }
+        }
+    }
+    numbers
+}
+
+/// Check that a commit message references existing work items, as the commit-msg hook
+/// does. With `prepend`, a message without a number gets the one from the branch name.
+///
+/// When the API can't be reached, the cached organization and work item numbers are used
+/// instead, and numbers the cache doesn't know are let through with a warning. So are
+/// numbers that can't be looked up for any other reason than the API saying so.
+pub async fn check_message(api: &Api, path: &Path, prepend: bool) -> Result<()> {
+    let message =
+        fs::read_to_string(path).with_context(|| format!("Could not read {:}", path.display()))?;
+    let text = message_text(&message);
+    let subject = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+    if ["Merge ", "Revert ", "fixup! ", "squash! ", "amend! "]
+        .iter()
+        .any(|p| subject.starts_with(p))
+    {
+        return Ok(());
+    }
+
+    let (org, online) = match resolve::organization(api).await {
+        Ok(org) => (org, true),
+        Err(e) => match cache::load::<Organization>(cache::ORGANIZATION, None) {
+            Some(org) => {
+                eprintln!(
+                    "{:} working offline from the cache ({:})",
+                    "warning:".yellow().bold(),
+                    e
+                );
+                (org, false)
+            }
+            None => {
+                // Not being able to check shouldn't stop anyone from committing
+                eprintln!(
+                    "{:} could not check the commit message ({:})",
+                    "warning:".yellow().bold(),
+                    e
+                );
+                return Ok(());
+            }
+        },
+    };
+
+    let mut numbers = numbers_in(&org, &text);
+    if numbers.is_empty() && prepend {
+        if let Some(number) = current_branch().ok().and_then(|b| number_in(&org, &b)) {
+            fs::write(path, format!("{:} {:}", number, message.trim_start()))
+                .with_context(|| format!("Could not write {:}", path.display()))?;
+            numbers.push(number);
+        }
+    }
+    if numbers.is_empty() {
+        bail!("The commit message doesn't reference a work item, e.g. ABC-123");
+    }
+
+    for number in numbers.iter() {
+        if online {
+            let item = match resolve::item(api, number).await {
+                Ok(item) => item,
+                // Only the API saying so means the number is wrong
+                Err(e) if e.downcast_ref::<QueryError>().is_some() => {
+                    bail!("{:} doesn't seem to exist: {:}", number, e)
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{:} could not check {:} ({:})",
+                        "warning:".yellow().bold(),
+                        number.bold(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if matches!(item.status.type_, StatusType::DONE | StatusType::ARCHIVED) {
+                eprintln!(
+                    "{:} {:} is already {:}",
+                    "warning:".yellow().bold(),
+                    number.bold(),
+                    item.status.name
+                );
+            }
+        } else {
+            let (key, n) = resolve::split_number(number)?;
+            let known = n
+                .parse::<u64>()
+                .is_ok_and(|n| complete::known_numbers(key).contains(&n));
+            if !known {
+                eprintln!(
+                    "{:} could not check {:} offline",
+                    "warning:".yellow().bold(),
+                    number.bold()
+                );
+            }
+        }
+    }
+    Ok(())
+}
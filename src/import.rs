@@ -0,0 +1,424 @@
+//! Creating work items in bulk from a markdown plan or a CSV file, `km item import`
+//!
+//! In markdown, every top-level bullet, numbered item or heading becomes a work item and
+//! the text nested under it becomes its description. `#label` and `@user` tokens in the
+//! title are taken out and added as labels and members.
+//!
+//! A CSV file needs a header row with a `title` column, and may also have `description`,
+//! `status`, `labels`, `members`, `watchers`, `effort` and `impact`. Labels, members and
+//! watchers are separated by commas or semicolons.
+
+use anyhow::{anyhow, Context, Result};
+
+use colored::*;
+
+use serde::Deserialize;
+
+use std::collections::HashMap;
+
+use crate::api::{create_work_item, Api, CreateWorkItem};
+use crate::config::Config;
+use crate::rank;
+use crate::resolve::{self, Organization, Space, Status};
+
+/// A work item to be created, as read from the file
+#[derive(Debug, Default)]
+pub struct Draft {
+    /// Line (markdown) or record (CSV) it came from, for error messages
+    pub line: usize,
+    pub title: String,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub labels: Vec<String>,
+    pub members: Vec<String>,
+    pub watchers: Vec<String>,
+    pub effort: Option<String>,
+    pub impact: Option<String>,
+}
+
+/// The title of a line that starts a new work item
+fn item_start(line: &str) -> Option<&str> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some(strip_checkbox(rest));
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some(strip_checkbox(rest));
+        }
+    }
+
+    let rest = line.trim_start_matches('#');
+    if rest.len() < line.len() && rest.starts_with(' ') {
+        return Some(rest.trim());
+    }
+    None
+}
+
+fn strip_checkbox(text: &str) -> &str {
+    for checkbox in ["[ ] ", "[x] ", "[X] "] {
+        if let Some(rest) = text.strip_prefix(checkbox) {
+            return rest.trim();
+        }
+    }
+    text.trim()
+}
+
+/// Nested lines with their common indentation removed, without blank lines around them
+fn description(lines: &[&str]) -> Option<String> {
+    let start = lines.iter().position(|l| !l.trim().is_empty())?;
+    let end = lines.iter().rposition(|l| !l.trim().is_empty())? + 1;
+    let lines = &lines[start..end];
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let text: Vec<&str> = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or("").trim_end())
+        .collect();
+    Some(text.join("\n"))
+}
+
+/// The ``` or ~~~ run a line starts with, which opens or closes a fenced code block
+fn fence(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    ['`', '~'].iter().find_map(|c| {
+        let len = line.chars().take_while(|x| x == c).count();
+        if len >= 3 {
+            Some(&line[..len])
+        } else {
+            None
+        }
+    })
+}
+
+pub fn parse_markdown(text: &str) -> Vec<Draft> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    // Nothing in a code block starts an item, like a `# comment` in a shell snippet
+    let mut starts: Vec<usize> = Vec::new();
+    let mut open: Option<&str> = None;
+    for (i, line) in lines.iter().enumerate() {
+        match (open, fence(line)) {
+            (None, Some(fence)) => open = Some(fence),
+            (Some(opened), Some(fence)) if fence.starts_with(opened) && line.trim() == fence => {
+                open = None
+            }
+            (None, None) if item_start(line).is_some() => starts.push(i),
+            _ => {}
+        }
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(n, &start)| {
+            let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+            let mut draft = Draft {
+                line: start + 1,
+                description: description(&lines[start + 1..end]),
+                ..Default::default()
+            };
+
+            let mut words = Vec::new();
+            for word in item_start(lines[start]).unwrap_or("").split_whitespace() {
+                match (word.strip_prefix('#'), word.strip_prefix('@')) {
+                    (Some(label), _) if !label.is_empty() => draft.labels.push(label.to_string()),
+                    (_, Some(user)) if !user.is_empty() => draft.members.push(user.to_string()),
+                    _ => words.push(word),
+                }
+            }
+            draft.title = words.join(" ");
+            draft
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Record {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    labels: Option<String>,
+    #[serde(default)]
+    members: Option<String>,
+    #[serde(default)]
+    watchers: Option<String>,
+    #[serde(default)]
+    effort: Option<String>,
+    #[serde(default)]
+    impact: Option<String>,
+}
+
+fn list(field: Option<String>) -> Vec<String> {
+    field
+        .unwrap_or_default()
+        .split([',', ';'])
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn parse_csv(text: &str) -> Result<Vec<Draft>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut drafts = Vec::new();
+    for (i, record) in reader.deserialize::<Record>().enumerate() {
+        // The header is line 1
+        let line = i + 2;
+        let record = record.with_context(|| format!("Invalid CSV record on line {:}", line))?;
+        drafts.push(Draft {
+            line,
+            title: record.title,
+            description: record.description,
+            status: record.status,
+            labels: list(record.labels),
+            members: list(record.members),
+            watchers: list(record.watchers),
+            effort: record.effort,
+            impact: record.impact,
+        });
+    }
+    Ok(drafts)
+}
+
+/// A draft with its names resolved, ready to be created
+struct Resolved<'a> {
+    draft: Draft,
+    status: &'a Status,
+    label_ids: Vec<String>,
+    member_ids: Vec<String>,
+    watcher_ids: Vec<String>,
+}
+
+fn resolve<'a>(
+    org: &'a Organization,
+    spc: &'a Space,
+    config: &Config,
+    default_status: &'a Status,
+    draft: Draft,
+) -> Result<Resolved<'a>> {
+    if draft.title.is_empty() {
+        return Err(anyhow!("Missing title"));
+    }
+    let status = match &draft.status {
+        Some(name) => resolve::status(spc, Some(name))?,
+        None => default_status,
+    };
+    let label_ids = draft
+        .labels
+        .iter()
+        .map(|name| resolve::label(spc, Some(name)).map(|l| l.id.clone()))
+        .collect::<Result<Vec<String>>>()?;
+    let users = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| resolve::user(org, config, Some(name)).map(|u| u.id.clone()))
+            .collect::<Result<Vec<String>>>()
+    };
+    Ok(Resolved {
+        member_ids: users(&draft.members)?,
+        watcher_ids: users(&draft.watchers)?,
+        status,
+        label_ids,
+        draft,
+    })
+}
+
+fn names(names: &[String], prefix: &str) -> String {
+    names
+        .iter()
+        .map(|n| format!("{:}{:}", prefix, n))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Create the drafts in order at the bottom of their status columns, or only show what
+/// would be created
+pub async fn run(
+    api: &Api,
+    org: &Organization,
+    spc: &Space,
+    config: &Config,
+    default_status: &Status,
+    drafts: Vec<Draft>,
+    dry_run: bool,
+) -> Result<()> {
+    if drafts.is_empty() {
+        println!("Nothing to import");
+        return Ok(());
+    }
+
+    // Check everything before creating anything, so a typo doesn't leave half a plan behind
+    let mut resolved = Vec::new();
+    for draft in drafts {
+        let line = draft.line;
+        resolved.push(
+            resolve(org, spc, config, default_status, draft)
+                .with_context(|| format!("Line {:}", line))?,
+        );
+    }
+
+    if dry_run {
+        println!(
+            "{:<6}{:<20}{:}",
+            "Line".bold().underline(),
+            "Status".bold().underline(),
+            "Title".bold().underline()
+        );
+        for r in resolved.iter() {
+            let draft = &r.draft;
+            let mut details = vec![names(&draft.labels, "#"), names(&draft.members, "@")];
+            if !draft.watchers.is_empty() {
+                details.push(format!("watchers: {:}", draft.watchers.join(", ")));
+            }
+            if let Some(effort) = &draft.effort {
+                details.push(format!("effort: {:}", effort));
+            }
+            if let Some(impact) = &draft.impact {
+                details.push(format!("impact: {:}", impact));
+            }
+            if let Some(description) = &draft.description {
+                details.push(format!(
+                    "{:} description lines",
+                    description.lines().count()
+                ));
+            }
+            details.retain(|d| !d.is_empty());
+            println!(
+                "{:<6}{:<20}{:} {:}",
+                draft.line,
+                r.status.name,
+                draft.title.bold(),
+                details.join(", ").dimmed()
+            );
+        }
+        println!(
+            "\n{:} work items would be created in {:}",
+            resolved.len(),
+            spc.key.bold()
+        );
+        return Ok(());
+    }
+
+    let total = resolved.len();
+    let mut last_sort: HashMap<String, Option<String>> = HashMap::new();
+    let mut created: Vec<(String, String)> = Vec::new();
+    let mut failed = 0;
+    for (i, r) in resolved.into_iter().enumerate() {
+        let result: Result<String> = async {
+            if !last_sort.contains_key(&r.status.id) {
+                let column =
+                    rank::column(api, &spc.id, &r.status.id, (&r.status.type_).into()).await?;
+                last_sort.insert(r.status.id.clone(), column.last().map(|i| i.sort.clone()));
+            }
+            let sort = rank::between(last_sort[&r.status.id].as_deref(), None)?;
+
+            let response_data = api
+                .query::<CreateWorkItem>(create_work_item::Variables {
+                    status_id: r.status.id.clone(),
+                    title: r.draft.title.clone(),
+                    description: r.draft.description.clone(),
+                    sort: Some(sort.clone()),
+                    member_ids: Some(r.member_ids.clone()),
+                    watcher_ids: Some(r.watcher_ids.clone()),
+                    label_ids: Some(r.label_ids.clone()),
+                    effort: r.draft.effort.clone(),
+                    impact: r.draft.impact.clone(),
+                })
+                .await?;
+            last_sort.insert(r.status.id.clone(), Some(sort));
+            Ok(format!(
+                "{:}-{:}",
+                spc.key, response_data.create_work_item.work_item.number
+            ))
+        }
+        .await;
+
+        match result {
+            Ok(number) => {
+                eprintln!("[{:}/{:}] Created {:}", i + 1, total, number.bold());
+                created.push((number, r.draft.title));
+            }
+            Err(e) => {
+                eprintln!(
+                    "[{:}/{:}] {:} line {:}: {:}",
+                    i + 1,
+                    total,
+                    "Failed".red(),
+                    r.draft.line,
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    if !created.is_empty() {
+        println!(
+            "\n{:<12}{:}",
+            "Number".bold().underline(),
+            "Title".bold().underline()
+        );
+        for (number, title) in created.iter() {
+            println!("{:<12}{:}", number.yellow(), title);
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(
+            "{:} of {:} work items could not be created",
+            failed,
+            total
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_blocks_in_descriptions() {
+        let text = "# Deploy script #ops
+Run this:
+
+```sh
+# build first
+- not an item
+```
+
+- Fix login @alex
+  ~~~~
+  ## nor this
+  ~~~
+  still code
+  ~~~~
+1. Last
+";
+        let drafts = parse_markdown(text);
+        let titles: Vec<&str> = drafts.iter().map(|d| d.title.as_str()).collect();
+        assert_eq!(titles, ["Deploy script", "Fix login", "Last"]);
+        assert_eq!(drafts[0].labels, ["ops"]);
+        assert_eq!(
+            drafts[0].description.as_deref(),
+            Some("Run this:\n\n```sh\n# build first\n- not an item\n```")
+        );
+        assert_eq!(drafts[1].members, ["alex"]);
+        assert_eq!(
+            drafts[1].description.as_deref(),
+            Some("~~~~\n## nor this\n~~~\nstill code\n~~~~")
+        );
+    }
+}
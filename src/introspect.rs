@@ -0,0 +1,285 @@
+//! Fetching the live schema through introspection and writing it out as SDL, for
+//! `km schema`
+
+use anyhow::{anyhow, Result};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use std::fmt::Write;
+
+use crate::api::Api;
+
+const QUERY: &str = r#"
+query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    types { ...FullType }
+  }
+}
+
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+    isDeprecated
+    deprecationReason
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) {
+    name
+    description
+    isDeprecated
+    deprecationReason
+  }
+  possibleTypes { ...TypeRef }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType { kind name ofType { kind name ofType { kind name ofType { kind name ofType { kind name } } } } }
+}
+"#;
+
+/// Scalars every GraphQL schema has, which SDL leaves out
+const BUILT_IN: [&str; 5] = ["String", "Int", "Float", "Boolean", "ID"];
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(rename = "__schema")]
+    schema: SchemaType,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaType {
+    query_type: Named,
+    mutation_type: Option<Named>,
+    types: Vec<FullType>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FullType {
+    kind: String,
+    name: String,
+    description: Option<String>,
+    fields: Option<Vec<Field>>,
+    input_fields: Option<Vec<InputValue>>,
+    interfaces: Option<Vec<TypeRef>>,
+    enum_values: Option<Vec<EnumValue>>,
+    possible_types: Option<Vec<TypeRef>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Field {
+    name: String,
+    description: Option<String>,
+    args: Vec<InputValue>,
+    #[serde(rename = "type")]
+    type_: TypeRef,
+    is_deprecated: bool,
+    deprecation_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InputValue {
+    name: String,
+    description: Option<String>,
+    #[serde(rename = "type")]
+    type_: TypeRef,
+    default_value: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EnumValue {
+    name: String,
+    description: Option<String>,
+    is_deprecated: bool,
+    deprecation_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TypeRef {
+    kind: String,
+    name: Option<String>,
+    of_type: Option<Box<TypeRef>>,
+}
+
+impl TypeRef {
+    /// The type as written in SDL, e.g. `[WorkItem!]!`
+    fn sdl(&self) -> String {
+        let inner = || self.of_type.as_ref().map(|t| t.sdl()).unwrap_or_default();
+        match self.kind.as_str() {
+            "NON_NULL" => format!("{:}!", inner()),
+            "LIST" => format!("[{:}]", inner()),
+            _ => self.name.clone().unwrap_or_default(),
+        }
+    }
+}
+
+fn description(out: &mut String, description: &Option<String>, indent: &str) {
+    if let Some(text) = description.as_deref().filter(|d| !d.is_empty()) {
+        let _ = writeln!(out, "{:}\"\"\"", indent);
+        for line in text.replace("\"\"\"", "\\\"\"\"").lines() {
+            let _ = writeln!(out, "{:}{:}", indent, line);
+        }
+        let _ = writeln!(out, "{:}\"\"\"", indent);
+    }
+}
+
+fn deprecated(is_deprecated: bool, reason: &Option<String>) -> String {
+    match (is_deprecated, reason.as_deref()) {
+        (false, _) => String::new(),
+        (true, None) | (true, Some("No longer supported")) => " @deprecated".to_string(),
+        (true, Some(reason)) => format!(
+            " @deprecated(reason: {:})",
+            serde_json::to_string(reason).unwrap_or_default()
+        ),
+    }
+}
+
+fn input_value(value: &InputValue) -> String {
+    match &value.default_value {
+        Some(default) => format!("{:}: {:} = {:}", value.name, value.type_.sdl(), default),
+        None => format!("{:}: {:}", value.name, value.type_.sdl()),
+    }
+}
+
+fn names(types: &Option<Vec<TypeRef>>) -> Vec<String> {
+    types
+        .iter()
+        .flatten()
+        .filter_map(|t| t.name.clone())
+        .collect()
+}
+
+/// Render the introspected schema as SDL
+fn sdl(schema: &SchemaType) -> String {
+    let mut out = String::new();
+
+    let mutation = schema.mutation_type.as_ref().map(|m| m.name.as_str());
+    if schema.query_type.name != "Query" || mutation.is_some_and(|m| m != "Mutation") {
+        let _ = writeln!(out, "schema {{");
+        let _ = writeln!(out, "  query: {:}", schema.query_type.name);
+        if let Some(mutation) = mutation {
+            let _ = writeln!(out, "  mutation: {:}", mutation);
+        }
+        let _ = writeln!(out, "}}\n");
+    }
+
+    for t in schema.types.iter() {
+        if t.name.starts_with("__") || BUILT_IN.contains(&t.name.as_str()) {
+            continue;
+        }
+        description(&mut out, &t.description, "");
+        match t.kind.as_str() {
+            "SCALAR" => {
+                let _ = writeln!(out, "scalar {:}", t.name);
+            }
+            "UNION" => {
+                let _ = writeln!(
+                    out,
+                    "union {:} = {:}",
+                    t.name,
+                    names(&t.possible_types).join(" | ")
+                );
+            }
+            "ENUM" => {
+                let _ = writeln!(out, "enum {:} {{", t.name);
+                for value in t.enum_values.iter().flatten() {
+                    description(&mut out, &value.description, "  ");
+                    let _ = writeln!(
+                        out,
+                        "  {:}{:}",
+                        value.name,
+                        deprecated(value.is_deprecated, &value.deprecation_reason)
+                    );
+                }
+                let _ = writeln!(out, "}}");
+            }
+            "INPUT_OBJECT" => {
+                let _ = writeln!(out, "input {:} {{", t.name);
+                for field in t.input_fields.iter().flatten() {
+                    description(&mut out, &field.description, "  ");
+                    let _ = writeln!(out, "  {:}", input_value(field));
+                }
+                let _ = writeln!(out, "}}");
+            }
+            kind => {
+                let keyword = if kind == "INTERFACE" {
+                    "interface"
+                } else {
+                    "type"
+                };
+                let interfaces = names(&t.interfaces);
+                if interfaces.is_empty() {
+                    let _ = writeln!(out, "{:} {:} {{", keyword, t.name);
+                } else {
+                    let _ = writeln!(
+                        out,
+                        "{:} {:} implements {:} {{",
+                        keyword,
+                        t.name,
+                        interfaces.join(" & ")
+                    );
+                }
+                for field in t.fields.iter().flatten() {
+                    description(&mut out, &field.description, "  ");
+                    let args: Vec<String> = field.args.iter().map(input_value).collect();
+                    let args = if args.is_empty() {
+                        String::new()
+                    } else {
+                        format!("({:})", args.join(", "))
+                    };
+                    let _ = writeln!(
+                        out,
+                        "  {:}{:}: {:}{:}",
+                        field.name,
+                        args,
+                        field.type_.sdl(),
+                        deprecated(field.is_deprecated, &field.deprecation_reason)
+                    );
+                }
+                let _ = writeln!(out, "}}");
+            }
+        }
+        out.push('\n');
+    }
+    out.trim_end().to_string() + "\n"
+}
+
+/// Fetch the live schema and render it as SDL
+pub async fn fetch_sdl(api: &Api) -> Result<String> {
+    let mut response = api.raw(&json!({ "query": QUERY })).await?;
+    if let Some(errors) = response.get("errors").filter(|e| !e.is_null()) {
+        return Err(anyhow!("Introspection failed: {:}", errors));
+    }
+    let data: Response = serde_json::from_value(response["data"].take())
+        .map_err(|e| anyhow!("Unexpected introspection response: {:}", e))?;
+    Ok(sdl(&data.schema))
+}
@@ -0,0 +1,835 @@
+//! Fetching, sorting, grouping and table output for `km item list`
+
+use anyhow::Result;
+
+use colored::*;
+
+use futures::stream::{self, StreamExt};
+
+use structopt::StructOpt;
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::str::FromStr;
+
+use crate::api::{items_query, items_query::StatusType, Api, WorkItem};
+use crate::color;
+use crate::complete;
+use crate::config::Config;
+use crate::filter::{all_status_types, Filter};
+use crate::progress::Progress;
+use crate::resolve::{Organization, Space};
+
+/// Space between columns
+const GAP: usize = 2;
+
+/// Columns other than the title are truncated to this width on a terminal
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// A work item together with the space it belongs to
+pub struct Row {
+    pub space_key: String,
+    pub space_name: String,
+    /// Position of the space in the organization, used to keep space groups in order
+    pub space_index: usize,
+    pub item: WorkItem,
+}
+
+impl Row {
+    pub fn key(&self) -> String {
+        format!("{:}-{:}", self.space_key, self.item.number)
+    }
+}
+
+// Which work items to list, shared by the commands that act on a list query. Not a doc
+// comment, as structopt would use it as the about text of every command flattening it.
+#[derive(StructOpt, Debug)]
+pub struct Query {
+    /// Optional space key
+    pub space: Option<String>,
+    /// List all (also backlog and done)
+    #[structopt(short, long)]
+    pub all: bool,
+    /// List only backlog
+    #[structopt(short, long)]
+    pub backlog: bool,
+    /// Only list items with these status types (backlog, todo, in-progress, done, archived)
+    #[structopt(
+        long,
+        use_delimiter = true,
+        conflicts_with_all = &["all", "backlog", "exclude-status-type"]
+    )]
+    pub status_type: Vec<StatusType>,
+    /// List all items except those with these status types
+    #[structopt(long, use_delimiter = true, conflicts_with_all = &["all", "backlog"])]
+    pub exclude_status_type: Vec<StatusType>,
+    /// Maximum number of items to list per space
+    #[structopt(short, long)]
+    pub limit: Option<usize>,
+    /// Filter expression, e.g. "type:in-progress label:bug member:me initiative:none".
+    /// Without status flags, only the status types the filter allows (or all but archived) are listed
+    #[structopt(short, long)]
+    pub filter: Option<String>,
+}
+
+impl Query {
+    /// The status types to fetch from the server
+    fn status_types(&self, filter: Option<&Filter>) -> Vec<StatusType> {
+        let flag_status_types = if !self.status_type.is_empty() {
+            Some(self.status_type.clone())
+        } else if !self.exclude_status_type.is_empty() {
+            Some(
+                all_status_types()
+                    .into_iter()
+                    .filter(|t| !self.exclude_status_type.contains(t))
+                    .collect(),
+            )
+        } else if self.all {
+            Some(
+                all_status_types()
+                    .into_iter()
+                    .filter(|t| *t != StatusType::ARCHIVED)
+                    .collect(),
+            )
+        } else if self.backlog {
+            Some(vec![StatusType::BACKLOG])
+        } else {
+            None
+        };
+        match (filter, flag_status_types) {
+            (None, Some(types)) => types,
+            (None, None) => vec![StatusType::TODO, StatusType::IN_PROGRESS],
+            (Some(f), Some(types)) => f
+                .status_types()
+                .into_iter()
+                .filter(|t| types.contains(t))
+                .collect(),
+            (Some(f), None) => {
+                let types = f.status_types();
+                if types.len() < all_status_types().len() {
+                    types
+                } else {
+                    types
+                        .into_iter()
+                        .filter(|t| *t != StatusType::ARCHIVED)
+                        .collect()
+                }
+            }
+        }
+    }
+
+    /// Fetch the matching work items of one space
+    async fn space_rows(
+        &self,
+        api: &Api,
+        spc: &Space,
+        space_index: usize,
+        status_filter: &items_query::StatusFilter,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<Row>> {
+        let items = match filter {
+            None => {
+                api.work_items(&spc.id, Some(status_filter.clone()), self.limit)
+                    .await?
+            }
+            Some(f) => {
+                let mut items: Vec<WorkItem> = api
+                    .work_items(&spc.id, Some(status_filter.clone()), None)
+                    .await?
+                    .into_iter()
+                    .filter(|item| f.matches(item))
+                    .collect();
+                if let Some(l) = self.limit {
+                    items.truncate(l);
+                }
+                items
+            }
+        };
+
+        complete::remember_items(&spc.key, items.iter().map(|i| i.number.clone()));
+
+        Ok(items
+            .into_iter()
+            .map(|item| Row {
+                space_key: spc.key.clone(),
+                space_name: spc.name.clone(),
+                space_index,
+                item,
+            })
+            .collect())
+    }
+
+    /// Fetch the matching work items, `jobs` spaces at a time, and hand each space's rows
+    /// to `section` as soon as they and those of the spaces before them are in
+    pub async fn rows_by_space<F: FnMut(Vec<Row>)>(
+        &self,
+        api: &Api,
+        org: &Organization,
+        config: &Config,
+        jobs: usize,
+        mut section: F,
+    ) -> Result<()> {
+        let filter = match &self.filter {
+            None => None,
+            Some(text) => Some(Filter::parse(text, config)?),
+        };
+        let status_filter = items_query::StatusFilter {
+            type_: items_query::StatusFilterType::INCLUDE,
+            filter: self.status_types(filter.as_ref()),
+        };
+        if status_filter.filter.is_empty() {
+            return Ok(());
+        }
+
+        let spaces: Vec<(usize, &Space)> = org
+            .spaces
+            .iter()
+            .enumerate()
+            .filter(|(_, spc)| {
+                self.space
+                    .as_ref()
+                    .is_none_or(|x| x.eq_ignore_ascii_case(&spc.key))
+            })
+            .collect();
+        let progress = Progress::new("Fetching spaces", spaces.len());
+
+        // Run up to `jobs` spaces at once, but hand them over in order: a slow space holds
+        // back the output of those after it, not their fetching
+        let mut sections = stream::iter(spaces.into_iter().enumerate())
+            .map(|(n, (space_index, spc))| {
+                let (status_filter, filter, progress) =
+                    (&status_filter, filter.as_ref(), &progress);
+                async move {
+                    let rows = self
+                        .space_rows(api, spc, space_index, status_filter, filter)
+                        .await;
+                    progress.tick();
+                    (n, rows)
+                }
+            })
+            .buffer_unordered(jobs.max(1));
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        while let Some((n, rows)) = sections.next().await {
+            pending.insert(n, rows?);
+            while let Some(rows) = pending.remove(&next) {
+                progress.clear();
+                section(rows);
+                progress.draw();
+                next += 1;
+            }
+        }
+        progress.clear();
+        Ok(())
+    }
+
+    /// Fetch the matching work items, in the order of their spaces
+    pub async fn rows(
+        &self,
+        api: &Api,
+        org: &Organization,
+        config: &Config,
+        jobs: usize,
+    ) -> Result<Vec<Row>> {
+        let mut rows = Vec::new();
+        self.rows_by_space(api, org, config, jobs, |section| rows.extend(section))
+            .await?;
+        Ok(rows)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    Status,
+    Key,
+    Title,
+    Labels,
+    Members,
+    Watchers,
+    Initiatives,
+    Space,
+    Sort,
+    Effort,
+    Impact,
+    Created,
+    Updated,
+    Closed,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "status" => Ok(Column::Status),
+            "key" | "number" => Ok(Column::Key),
+            "title" => Ok(Column::Title),
+            "labels" | "label" => Ok(Column::Labels),
+            "members" | "member" => Ok(Column::Members),
+            "watchers" | "watcher" => Ok(Column::Watchers),
+            "initiatives" | "initiative" => Ok(Column::Initiatives),
+            "space" => Ok(Column::Space),
+            "sort" => Ok(Column::Sort),
+            "effort" => Ok(Column::Effort),
+            "impact" => Ok(Column::Impact),
+            "created" => Ok(Column::Created),
+            "updated" => Ok(Column::Updated),
+            "closed" => Ok(Column::Closed),
+            _ => Err(format!("Unknown column {:}", s)),
+        }
+    }
+}
+
+impl Column {
+    pub fn defaults() -> Vec<Column> {
+        vec![Column::Status, Column::Key, Column::Title, Column::Labels]
+    }
+
+    /// The key comes first in tab-separated output, so it can be piped to other commands
+    pub fn tsv_defaults() -> Vec<Column> {
+        vec![Column::Key, Column::Status, Column::Title, Column::Labels]
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            Column::Status => "Status",
+            Column::Key => "Key",
+            Column::Title => "Title",
+            Column::Labels => "Labels",
+            Column::Members => "Members",
+            Column::Watchers => "Watchers",
+            Column::Initiatives => "Initiatives",
+            Column::Space => "Space",
+            Column::Sort => "Sort",
+            Column::Effort => "Effort",
+            Column::Impact => "Impact",
+            Column::Created => "Created",
+            Column::Updated => "Updated",
+            Column::Closed => "Closed",
+        }
+    }
+
+    fn cell(&self, row: &Row) -> Cell {
+        let item = &row.item;
+        let join = |names: Vec<&str>| Cell::plain(names.join(", "));
+        match self {
+            Column::Status => Cell::plain(item.status.name.clone()),
+            Column::Key => Cell::plain(row.key()),
+            Column::Title => Cell::plain(item.title.clone()),
+            Column::Labels => Cell {
+                segments: item
+                    .labels
+                    .iter()
+                    .map(|l| (l.name.clone(), Some(color::label_rgb(&l.color))))
+                    .collect(),
+                italic: true,
+            },
+            Column::Members => join(item.members.iter().map(|m| m.username.as_str()).collect()),
+            Column::Watchers => join(item.watchers.iter().map(|w| w.username.as_str()).collect()),
+            Column::Initiatives => {
+                join(item.initiatives.iter().map(|i| i.title.as_str()).collect())
+            }
+            Column::Space => Cell::plain(row.space_key.clone()),
+            Column::Sort => Cell::plain(item.sort.clone()),
+            Column::Effort => Cell::plain(item.effort.clone().unwrap_or_default()),
+            Column::Impact => Cell::plain(item.impact.clone().unwrap_or_default()),
+            Column::Created => Cell::plain(item.created_at.to_string()),
+            Column::Updated => Cell::plain(item.updated_at.to_string()),
+            Column::Closed => {
+                Cell::plain(item.closed_at.map(|d| d.to_string()).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// The text cut to `width` characters, ending in … if anything was cut
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+type Segment = (String, Option<(u8, u8, u8)>);
+
+/// Text in a table cell, optionally made of separately colored segments
+struct Cell {
+    segments: Vec<Segment>,
+    italic: bool,
+}
+
+impl Cell {
+    fn plain(text: String) -> Cell {
+        Cell {
+            segments: vec![(text, None)],
+            italic: false,
+        }
+    }
+
+    fn width(&self) -> usize {
+        let text: usize = self.segments.iter().map(|(s, _)| s.chars().count()).sum();
+        text + self.segments.len().saturating_sub(1)
+    }
+
+    /// Render truncated to `width` characters, padding if `pad` is set
+    fn render(&self, width: usize, pad: bool) -> String {
+        let mut out = String::new();
+        let mut used = 0;
+        for (i, (text, rgb)) in self.segments.iter().enumerate() {
+            if i > 0 {
+                if used + 1 >= width {
+                    break;
+                }
+                out.push(' ');
+                used += 1;
+            }
+            let text = truncate(text, width - used);
+            used += text.chars().count();
+            let mut styled = match rgb {
+                Some((r, g, b)) => text.truecolor(*r, *g, *b),
+                None => text.normal(),
+            };
+            if self.italic {
+                styled = styled.italic();
+            }
+            out.push_str(&styled.to_string());
+            if used >= width {
+                break;
+            }
+        }
+        if pad && used < width {
+            out.push_str(&" ".repeat(width - used));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SortField {
+    Status,
+    Number,
+    Sort,
+    Updated,
+    Created,
+    Effort,
+    Impact,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    /// `field`, `field:desc` or `-field`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, descending) = match s.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => match s.split_once(':') {
+                Some((name, "desc")) => (name, true),
+                Some((name, "asc")) => (name, false),
+                Some(_) => return Err(format!("Unknown sort order in {:}", s)),
+                None => (s, false),
+            },
+        };
+        let field = match name.to_lowercase().as_str() {
+            "status" => SortField::Status,
+            "number" | "key" => SortField::Number,
+            "sort" => SortField::Sort,
+            "updated" | "updatedat" => SortField::Updated,
+            "created" | "createdat" => SortField::Created,
+            "effort" => SortField::Effort,
+            "impact" => SortField::Impact,
+            _ => return Err(format!("Unknown sort key {:}", name)),
+        };
+        Ok(SortKey { field, descending })
+    }
+}
+
+/// Orders status types as work moves through them, from backlog to archived
+pub fn status_type_rank(status_type: &StatusType) -> usize {
+    match status_type {
+        StatusType::BACKLOG => 0,
+        StatusType::TODO => 1,
+        StatusType::IN_PROGRESS => 2,
+        StatusType::DONE => 3,
+        StatusType::ARCHIVED => 4,
+        StatusType::Other(_) => 5,
+    }
+}
+
+/// Orders effort/impact values from small to large, with unset values last
+pub fn size_rank(value: &Option<String>) -> (usize, String) {
+    let value = match value {
+        None => return (usize::MAX, String::new()),
+        Some(v) => v.to_uppercase(),
+    };
+    let rank = match value.as_str() {
+        "XS" | "EXTRA_SMALL" | "TINY" | "NONE" => 0,
+        "S" | "SMALL" | "LOW" => 1,
+        "M" | "MEDIUM" => 2,
+        "L" | "LARGE" | "HIGH" => 3,
+        "XL" | "EXTRA_LARGE" | "HUGE" | "CRITICAL" => 4,
+        _ => value.parse::<usize>().map_or(5, |n| n.saturating_add(5)),
+    };
+    (rank, value)
+}
+
+fn number(item: &WorkItem) -> u64 {
+    item.number.parse().unwrap_or(u64::MAX)
+}
+
+fn compare(a: &Row, b: &Row, key: &SortKey) -> Ordering {
+    let (a, b) = (&a.item, &b.item);
+    let ordering = match key.field {
+        SortField::Status => (status_type_rank(&a.status.type_), &a.status.name)
+            .cmp(&(status_type_rank(&b.status.type_), &b.status.name)),
+        SortField::Number => number(a).cmp(&number(b)),
+        SortField::Sort => a.sort.cmp(&b.sort),
+        SortField::Updated => a.updated_at.cmp(&b.updated_at),
+        SortField::Created => a.created_at.cmp(&b.created_at),
+        SortField::Effort => size_rank(&a.effort).cmp(&size_rank(&b.effort)),
+        SortField::Impact => size_rank(&a.impact).cmp(&size_rank(&b.impact)),
+    };
+    if key.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// Stable sort by each key in turn
+pub fn sort(rows: &mut [Row], keys: &[SortKey]) {
+    rows.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| compare(a, b, key))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    None,
+    Status,
+    Label,
+    Member,
+    Initiative,
+    Space,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(GroupBy::None),
+            "status" => Ok(GroupBy::Status),
+            "label" => Ok(GroupBy::Label),
+            "member" => Ok(GroupBy::Member),
+            "initiative" => Ok(GroupBy::Initiative),
+            "space" => Ok(GroupBy::Space),
+            _ => Err(format!("Unknown grouping {:}", s)),
+        }
+    }
+}
+
+/// A group header and the rows in it. Items with several labels, members or initiatives
+/// show up in each of their groups.
+pub struct Group<'a> {
+    pub title: String,
+    /// The status, space, label, member or initiative the group is for; `None` for items
+    /// without any, or when not grouping
+    pub name: Option<String>,
+    /// Groups are ordered by this, then by title
+    rank: (usize, String),
+    pub rows: Vec<&'a Row>,
+}
+
+pub fn groups(rows: &[Row], group_by: GroupBy) -> Vec<Group<'_>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for row in rows {
+        let item = &row.item;
+        let keys: Vec<(String, Option<String>, (usize, String))> = match group_by {
+            GroupBy::None => vec![(String::new(), None, (0, String::new()))],
+            GroupBy::Status => vec![(
+                format!("Status: {:}", item.status.name),
+                Some(item.status.name.clone()),
+                (
+                    status_type_rank(&item.status.type_),
+                    item.status.name.clone(),
+                ),
+            )],
+            GroupBy::Space => vec![(
+                format!("Space: {:}", row.space_name),
+                Some(row.space_name.clone()),
+                (row.space_index, String::new()),
+            )],
+            GroupBy::Label => item
+                .labels
+                .iter()
+                .map(|l| {
+                    (
+                        format!("Label: {:}", l.name),
+                        Some(l.name.clone()),
+                        (0, l.name.to_lowercase()),
+                    )
+                })
+                .collect(),
+            GroupBy::Member => item
+                .members
+                .iter()
+                .map(|m| {
+                    (
+                        format!("Member: {:}", m.username),
+                        Some(m.username.clone()),
+                        (0, m.username.clone()),
+                    )
+                })
+                .collect(),
+            GroupBy::Initiative => item
+                .initiatives
+                .iter()
+                .map(|i| {
+                    (
+                        format!("Initiative: {:}", i.title),
+                        Some(i.title.clone()),
+                        (0, i.title.to_lowercase()),
+                    )
+                })
+                .collect(),
+        };
+        let keys = if keys.is_empty() {
+            let none = match group_by {
+                GroupBy::Label => "No label",
+                GroupBy::Member => "No member",
+                _ => "No initiative",
+            };
+            vec![(none.to_string(), None, (usize::MAX, String::new()))]
+        } else {
+            keys
+        };
+
+        for (title, name, rank) in keys {
+            match groups.iter_mut().find(|g| g.title == title) {
+                Some(group) => group.rows.push(row),
+                None => groups.push(Group {
+                    title,
+                    name,
+                    rank,
+                    rows: vec![row],
+                }),
+            }
+        }
+    }
+    groups.sort_by(|a, b| a.rank.cmp(&b.rank));
+    groups
+}
+
+/// How `km item list` prints its rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Table,
+    Tsv,
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Output::Table),
+            "tsv" => Ok(Output::Tsv),
+            _ => Err(format!("Unknown output {:} (table or tsv)", s)),
+        }
+    }
+}
+
+/// Print the rows as tab-separated values without a header or colors, one row per line
+pub fn print_tsv(rows: &[Row], columns: &[Column]) {
+    for row in rows {
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                let cell = c.cell(row);
+                let texts: Vec<&str> = cell
+                    .segments
+                    .iter()
+                    .map(|(text, _)| text.as_str())
+                    .collect();
+                texts.join(",").replace(['\t', '\n', '\r'], " ")
+            })
+            .collect();
+        println!("{:}", cells.join("\t"));
+    }
+}
+
+/// Print the rows as a table, optionally split into groups with a header and count each
+pub fn print(rows: &[Row], columns: &[Column], group_by: GroupBy) {
+    let tty = std::io::stdout().is_terminal();
+
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .map(|c| {
+            rows.iter()
+                .map(|r| c.cell(r).width())
+                .max()
+                .unwrap_or(0)
+                .max(c.header().len())
+        })
+        .collect();
+
+    // On a terminal, cap the other columns and let the title (or the last column) take
+    // whatever is left
+    if tty {
+        let (terminal_width, _) = termimad::terminal_size();
+        let flexible = columns
+            .iter()
+            .position(|c| *c == Column::Title)
+            .unwrap_or(columns.len().saturating_sub(1));
+        for (i, width) in widths.iter_mut().enumerate() {
+            if i != flexible {
+                *width = (*width).min(MAX_COLUMN_WIDTH);
+            }
+        }
+        let others: usize = widths
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != flexible)
+            .map(|(_, w)| w + GAP)
+            .sum();
+        if let Some(width) = widths.get_mut(flexible) {
+            let available = (terminal_width as usize).saturating_sub(others).max(10);
+            *width = (*width).min(available);
+        }
+    }
+
+    let print_header = || {
+        let headers: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (c, w))| {
+                let pad = if i + 1 < columns.len() {
+                    " ".repeat(w.saturating_sub(c.header().len()) + GAP)
+                } else {
+                    String::new()
+                };
+                format!("{:}{:}", c.header().bold().underline(), pad)
+            })
+            .collect();
+        println!("{:}", headers.concat());
+    };
+
+    let print_row = |row: &Row| {
+        let cells: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(i, (c, w))| {
+                let last = i + 1 == columns.len();
+                let width = if tty { *w } else { usize::MAX };
+                let rendered = c.cell(row).render(width, !last && tty);
+                if last {
+                    rendered
+                } else if tty {
+                    rendered + &" ".repeat(GAP)
+                } else {
+                    let padding = w.saturating_sub(c.cell(row).width()) + GAP;
+                    rendered + &" ".repeat(padding)
+                }
+            })
+            .collect();
+        println!("{:}", cells.concat());
+    };
+
+    if group_by == GroupBy::None {
+        print_header();
+        rows.iter().for_each(print_row);
+        return;
+    }
+
+    for (i, group) in groups(rows, group_by).iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!(
+            "{:} {:}",
+            group.title.bold(),
+            format!("({:})", group.rows.len()).dimmed()
+        );
+        print_header();
+        group.rows.iter().for_each(|r| print_row(r));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::api::{self, space_query, SpaceQuery};
+    use crate::{fixture, trace};
+
+    /// An API answering from the recordings in `tests/fixtures/NAME`
+    fn replay(name: &str) -> Api {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        Api::new(
+            String::new(),
+            api::Options {
+                endpoint: None,
+                timeout: Duration::from_secs(1),
+                retries: 0,
+                retry_mutations: false,
+                proxy: None,
+                no_proxy: None,
+                ca_certs: Vec::new(),
+                client_cert: None,
+                client_key: None,
+                client_cert_password: None,
+                user_agent: None,
+                log_level: trace::Level::Off,
+                trace_file: None,
+                fixtures: Some(fixture::Mode::Replay(dir)),
+            },
+        )
+        .unwrap()
+    }
+
+    async fn keys(api: &Api, args: &[&str]) -> Result<Vec<String>> {
+        let org = api
+            .query::<SpaceQuery>(space_query::Variables {})
+            .await?
+            .organization;
+        let query = Query::from_iter_safe(["list"].iter().chain(args))?;
+        let rows = query.rows(api, &org, &Config::default(), 4).await?;
+        Ok(rows.iter().map(Row::key).collect())
+    }
+
+    #[tokio::test]
+    async fn rows_from_recording() {
+        let api = replay("item-list");
+        assert_eq!(keys(&api, &["s0"]).await.unwrap(), ["S0-1", "S0-2"]);
+        assert_eq!(
+            keys(&api, &["S0", "--filter", "title:\"item 1\""])
+                .await
+                .unwrap(),
+            ["S0-2"]
+        );
+        // Archived items are a different request, which wasn't recorded
+        assert!(keys(&api, &["S0", "--status-type", "archived"])
+            .await
+            .is_err());
+    }
+}
@@ -1,66 +1,303 @@
+use structopt::clap::ArgGroup;
 use structopt::StructOpt;
 
-use graphql_client::{GraphQLQuery, Response};
+use anyhow::anyhow;
 
 use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use colored::*;
 
-use colors_transform::Color;
-use colors_transform::Rgb;
-
-use std::collections::HashMap;
-
-// GraphQL queries
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "src/kitemaker.graphql",
-    query_path = "src/queries.graphql",
-    response_derives = "Debug,PartialEq"
-)]
-struct SpaceQuery;
-
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "src/kitemaker.graphql",
-    query_path = "src/queries.graphql",
-    response_derives = "Debug,PartialEq"
-)]
-struct ItemsQuery;
-
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "src/kitemaker.graphql",
-    query_path = "src/queries.graphql",
-    response_derives = "Debug,PartialEq"
-)]
-struct ItemQuery;
-
-#[derive(GraphQLQuery)]
-#[graphql(
-    schema_path = "src/kitemaker.graphql",
-    query_path = "src/queries.graphql",
-    response_derives = "Debug,PartialEq"
-)]
-struct CreateWorkItem;
+mod api;
+mod board;
+mod bulk;
+mod cache;
+mod changelog;
+mod color;
+mod complete;
+mod config;
+mod filter;
+mod fixture;
+mod git;
+mod import;
+mod introspect;
+mod list;
+mod matrix;
+mod picker;
+mod progress;
+mod rank;
+mod raw;
+mod resolve;
+mod retry;
+mod review;
+mod schema;
+mod search;
+mod template;
+mod trace;
+
+use api::{
+    add_labels_to_work_item, add_members_to_work_item, comment_on_work_item, create_work_item,
+    edit_work_item, item_query, items_query, move_work_item, rank_work_item,
+    remove_labels_from_work_item, remove_members_from_work_item, AddLabelsToWorkItem,
+    AddMembersToWorkItem, Api, CommentOnWorkItem, CreateWorkItem, EditWorkItem, ItemQuery,
+    MoveWorkItem, RankWorkItem, RemoveLabelsFromWorkItem, RemoveMembersFromWorkItem,
+};
+use config::Config;
+use filter::Filter;
 
 /// Command line tool for Kitemaker
 #[derive(StructOpt)]
 #[structopt(about = "Command line tool for Kitemaker")]
 struct Cli {
+    /// API token (required except with --replay)
     #[structopt(short, long, env = "KM_TOKEN")]
-    token: String,
+    token: Option<String>,
+
+    /// GraphQL endpoint (default: from the config, or Kitemaker's)
+    #[structopt(long, env = "KM_ENDPOINT")]
+    endpoint: Option<String>,
+
+    /// Seconds to wait for a response (default: from the config, or 30)
+    #[structopt(long, env = "KM_TIMEOUT")]
+    timeout: Option<u64>,
+
+    /// Times a query is sent again after a timeout, 429 or gateway error (default: from the
+    /// config, or 3)
+    #[structopt(long, env = "KM_RETRIES")]
+    retries: Option<u32>,
+
+    /// Also retry mutations, which may then be applied twice
+    #[structopt(long)]
+    retry_mutations: bool,
+
+    /// Log API requests on standard error: -v for operations, timing and sizes, -vv to
+    /// add variables and page cursors
+    #[structopt(short, long, parse(from_occurrences))]
+    verbose: u64,
+
+    /// Log level: off, info (same as -v) or debug (same as -vv)
+    #[structopt(long, env = "KM_LOG")]
+    log: Option<trace::Level>,
+
+    /// Append every request and response to this file as JSON lines, with the token
+    /// redacted
+    #[structopt(long)]
+    trace_file: Option<PathBuf>,
+
+    /// Save every response in this directory, keyed by operation and variables
+    #[structopt(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer requests from responses saved with --record instead of the network
+    #[structopt(long, value_name = "DIR")]
+    replay: Option<PathBuf>,
 
     #[structopt(subcommand)]
     cmd: Commands,
 }
 
 #[derive(StructOpt)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// List organization name, members, and all spaces
     Organization,
     /// Work items subcommands
     Item(SubCommands),
+    /// Open a kanban board for a space
+    Board {
+        /// Key for the space; picked interactively if left out
+        space: Option<String>,
+    },
+    /// Search work item titles and descriptions
+    Search {
+        /// Words to search for; use "quotes" for phrases
+        query: String,
+        /// Only search this space
+        #[structopt(short, long)]
+        space: Option<String>,
+        /// Also search comments
+        #[structopt(short, long)]
+        comments: bool,
+        /// Include archived work items
+        #[structopt(long)]
+        archived: bool,
+        /// Maximum number of results
+        #[structopt(short, long, default_value = "20")]
+        limit: usize,
+        /// Ignore the local cache and fetch all work items again
+        #[structopt(short, long)]
+        refresh: bool,
+    },
+    /// Compare the bundled GraphQL schema with the API's
+    Schema(SchemaSubCommands),
+    /// Branches named after work items
+    Git(GitSubCommands),
+    /// Pull and merge requests linked to work items
+    Review(ReviewSubCommands),
+    /// Write release notes from the work items closed in a date range
+    #[structopt(group = ArgGroup::with_name("start").required(true))]
+    Changelog {
+        /// Items closed on or after this date (e.g. 2026-09-01 or 30d)
+        #[structopt(long, group = "start")]
+        since: Option<String>,
+        /// Items closed on or before this date (default: now)
+        #[structopt(long, conflicts_with = "to-tag")]
+        until: Option<String>,
+        /// Items closed after this git tag was made, instead of --since
+        #[structopt(long, group = "start")]
+        from_tag: Option<String>,
+        /// Items closed before this git tag was made, instead of --until
+        #[structopt(long)]
+        to_tag: Option<String>,
+        /// Only this space
+        #[structopt(long)]
+        space: Option<String>,
+        /// Only items with this label
+        #[structopt(long)]
+        label: Option<String>,
+        /// Only items matching this filter expression, as for `km item list`
+        #[structopt(short, long)]
+        filter: Option<String>,
+        /// Group by label, initiative, space, member or none
+        #[structopt(short, long, default_value = "label")]
+        group_by: list::GroupBy,
+        /// Template file to write the changelog with (default: markdown)
+        #[structopt(short = "T", long)]
+        template: Option<PathBuf>,
+        /// Number of spaces to fetch at once
+        #[structopt(short, long, default_value = "4")]
+        jobs: usize,
+    },
+    /// Print a shell completion script, e.g. `source <(km completions bash)`
+    Completions {
+        /// bash, zsh or fish
+        #[structopt(possible_values = &["bash", "zsh", "fish"])]
+        shell: String,
+    },
+    /// Send a GraphQL query or mutation and print the JSON response
+    Api {
+        /// The query, @FILE to read it from a file, or - to read it from standard input
+        query: String,
+        /// Add a variable as NAME=VALUE; the value is parsed as JSON if it can be (e.g.
+        /// 10, true, null or ["a"]) and taken as a string otherwise
+        #[structopt(short = "F", long = "field", number_of_values = 1)]
+        fields: Vec<String>,
+        /// Add a string variable as NAME=VALUE, taking the value as is
+        #[structopt(short = "f", long = "raw-field", number_of_values = 1)]
+        raw_fields: Vec<String>,
+        /// Name of the operation to run, when the query has several
+        #[structopt(long)]
+        operation: Option<String>,
+        /// Check the query against the bundled schema before sending it
+        #[structopt(long)]
+        validate: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct SchemaSubCommands {
+    #[structopt(subcommand)]
+    cmd: SchemaCommand,
+}
+
+#[derive(StructOpt, Debug)]
+enum SchemaCommand {
+    /// Report types, fields, arguments and enum values the API added or removed since the
+    /// bundled schema was written; fails if anything was removed or changed
+    Check {
+        /// Compare with this SDL file instead of introspecting the API
+        #[structopt(short, long)]
+        file: Option<String>,
+    },
+    /// Write the API's schema as SDL, e.g. to refresh src/kitemaker.graphql
+    Dump {
+        /// Write to this file instead of standard output
+        #[structopt(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct GitSubCommands {
+    #[structopt(subcommand)]
+    cmd: GitCommand,
+}
+
+#[derive(StructOpt, Debug)]
+enum GitCommand {
+    /// Create and check out a branch for a work item, e.g. abc-123-fix-login-timeout
+    Branch {
+        /// The number with space key (e.g., ABC-123) for the work item; picked
+        /// interactively if left out
+        number: Option<String>,
+        /// Branch name pattern (default: from the config, or {key}-{number}-{title})
+        #[structopt(short, long)]
+        pattern: Option<String>,
+        /// Also move the work item to an in-progress status when the branch is created
+        /// (default: from the config)
+        #[structopt(short, long)]
+        start: bool,
+        /// Don't move the work item, even if the config says to
+        #[structopt(long, conflicts_with = "start")]
+        no_start: bool,
+    },
+    /// Show the work item the current branch is named after
+    Current {
+        /// Only print its number, without asking the API about it
+        #[structopt(short, long)]
+        number: bool,
+    },
+    /// Install a commit-msg hook that rejects commits not referencing an existing work item
+    InstallHook {
+        /// Have the hook add the number from the branch name to messages without one
+        #[structopt(short, long)]
+        prepend: bool,
+        /// Replace a commit-msg hook that km didn't install
+        #[structopt(short, long)]
+        force: bool,
+    },
+    /// Check the work items a commit message file references, as the hook does; works
+    /// offline from the cache when the API can't be reached
+    CheckMessage {
+        /// The commit message file
+        file: PathBuf,
+        /// Add the number from the branch name to a message without one
+        #[structopt(short, long)]
+        prepend: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+struct ReviewSubCommands {
+    #[structopt(subcommand)]
+    cmd: ReviewCommand,
+}
+
+#[derive(StructOpt, Debug)]
+enum ReviewCommand {
+    /// Show a pull or merge request with its state, links and work items
+    Show {
+        /// URL of the pull or merge request
+        url: String,
+    },
+    /// Add or remove the links shown with a pull or merge request
+    Links {
+        /// URL of the pull or merge request
+        url: String,
+        /// Link to add as NAME=URL, replacing one with the same name; can be repeated
+        #[structopt(
+            short,
+            long,
+            value_name = "NAME=URL",
+            number_of_values = 1,
+            parse(try_from_str = review::parse_link)
+        )]
+        add: Vec<(String, String)>,
+        /// Name of a link to remove; can be repeated
+        #[structopt(short, long, value_name = "NAME", number_of_values = 1)]
+        remove: Vec<String>,
+    },
 }
 
 #[derive(StructOpt, Debug)]
@@ -73,89 +310,353 @@ struct SubCommands {
 enum Item {
     /// List all work items
     List {
-        /// Optional space key
-        space: Option<String>,
-        /// List all (also backlog and done)
+        #[structopt(flatten)]
+        query: list::Query,
+        /// Save the filter expression in the config under this name, to be used as @NAME
+        #[structopt(long, requires = "filter")]
+        save_filter: Option<String>,
+        /// Sort by status, number, sort, updated, created, effort or impact; append :desc
+        /// (or prefix with -) to reverse. Several keys can be given separated by commas
+        #[structopt(
+            short,
+            long,
+            use_delimiter = true,
+            allow_hyphen_values = true,
+            number_of_values = 1
+        )]
+        sort: Vec<list::SortKey>,
+        /// Group by status, label, member, initiative, space or none (default: space when
+        /// listing all spaces)
         #[structopt(short, long)]
-        all: bool,
-        /// List only backlog
-        #[structopt(short, long)]
-        backlog: bool,
+        group_by: Option<list::GroupBy>,
+        /// Columns to show: status, key, title, labels, members, watchers, initiatives,
+        /// space, sort, effort, impact, created, updated, closed (default:
+        /// status,key,title,labels)
+        #[structopt(short, long, use_delimiter = true)]
+        columns: Vec<list::Column>,
+        /// Print a table, or tab-separated values without a header for scripts (default
+        /// columns key,status,title,labels)
+        #[structopt(short, long, default_value = "table")]
+        output: list::Output,
+        /// How many spaces to fetch at the same time
+        #[structopt(short, long, default_value = "4")]
+        jobs: usize,
     },
 
     /// Create a new work item
     Create {
-        /// Key for the target space
-        space: String,
-        /// Title of the new work item
-        title: String,
+        /// Key for the target space; picked interactively if left out. A single argument
+        /// is taken as the space if it names one, and as the title otherwise.
+        space: Option<String>,
+        /// Title of the new work item; asked for if left out
+        title: Option<String>,
         /// Description as markdown formatted text
         description: Option<String>,
+        /// Name of the status (default: the space's default backlog or todo status)
+        #[structopt(long)]
+        status: Option<String>,
+        /// Labels to add, by name
+        #[structopt(short, long, use_delimiter = true)]
+        label: Vec<String>,
+        /// Members to assign, by username or "me"
+        #[structopt(short, long, use_delimiter = true)]
+        member: Vec<String>,
+        /// Watchers to add, by username or "me"
+        #[structopt(short, long, use_delimiter = true)]
+        watcher: Vec<String>,
+        /// Put the new work item at the top or bottom of its status column
+        #[structopt(long, default_value = "top")]
+        position: rank::Position,
+        /// Use this sort key instead of a position
+        #[structopt(long, conflicts_with = "position")]
+        sort: Option<String>,
+        /// Effort, e.g. S, M or L
+        #[structopt(long)]
+        effort: Option<String>,
+        /// Impact, e.g. LOW, MEDIUM or HIGH
+        #[structopt(long)]
+        impact: Option<String>,
+        /// Start from the template NAME.md in .km/templates/ or the config directory,
+        /// asking for its placeholders; flags take precedence over its defaults
+        #[structopt(short = "T", long)]
+        template: Option<String>,
     },
 
-    /// View a work item
+    /// Change the title, description, effort or impact of work items
+    #[structopt(group = ArgGroup::with_name("change").required(true).multiple(true))]
+    Edit {
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input; picked interactively if left out
+        numbers: Vec<String>,
+        /// New title
+        #[structopt(long, group = "change")]
+        title: Option<String>,
+        /// New description as markdown formatted text
+        #[structopt(long, group = "change")]
+        description: Option<String>,
+        /// New effort, e.g. S, M or L
+        #[structopt(long, group = "change")]
+        effort: Option<String>,
+        /// New impact, e.g. LOW, MEDIUM or HIGH
+        #[structopt(long, group = "change")]
+        impact: Option<String>,
+    },
+
+    /// View work items
     View {
-        /// The number with space key (e.g., ABC-123) for the work item
-        number: String,
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input; picked interactively if left out
+        numbers: Vec<String>,
     },
+
+    /// Move work items to another status
+    Move {
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input, followed by the name of the target status; each is picked
+        /// interactively if left out
+        #[structopt(value_name = "NUMBER... STATUS")]
+        args: Vec<String>,
+    },
+
+    /// Change where a work item appears in its status column
+    #[structopt(group = ArgGroup::with_name("position").required(true))]
+    Rank {
+        /// The number with space key (e.g., ABC-123) for the work item; picked
+        /// interactively if left out
+        number: Option<String>,
+        /// Place it right above this work item (moving it to that item's status if needed)
+        #[structopt(long, group = "position")]
+        before: Option<String>,
+        /// Place it right below this work item (moving it to that item's status if needed)
+        #[structopt(long, group = "position")]
+        after: Option<String>,
+        /// Place it at the top of its column
+        #[structopt(long, group = "position")]
+        top: bool,
+        /// Place it at the bottom of its column
+        #[structopt(long, group = "position")]
+        bottom: bool,
+    },
+
+    /// Add a member to (or remove one from) work items
+    Assign {
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input, followed by a username or "me"; each is picked
+        /// interactively if left out
+        #[structopt(value_name = "NUMBER... USER")]
+        args: Vec<String>,
+        /// Remove the member instead
+        #[structopt(short, long)]
+        remove: bool,
+    },
+
+    /// Add a label to (or remove one from) work items
+    Label {
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input, followed by the label name; each is picked interactively if
+        /// left out
+        #[structopt(value_name = "NUMBER... LABEL")]
+        args: Vec<String>,
+        /// Remove the label instead
+        #[structopt(short, long)]
+        remove: bool,
+    },
+
+    /// Comment on work items
+    Comment {
+        /// The numbers with space key (e.g., ABC-123) for the work items, or - to read them
+        /// from standard input, followed by the comment as markdown formatted text; each is
+        /// picked interactively if left out
+        #[structopt(value_name = "NUMBER... COMMENT")]
+        args: Vec<String>,
+    },
+
+    /// Create work items from a markdown plan or a CSV file
+    ///
+    /// In markdown, every top-level bullet, numbered item or heading becomes a work item,
+    /// with the text nested under it as the description. #label and @user tokens in its
+    /// title add labels and members.
+    ///
+    /// CSV files need a header row with a title column, and may have description, status,
+    /// labels, members, watchers, effort and impact columns.
+    ///
+    /// Work items are added to the bottom of their status column in file order.
+    Import {
+        /// Markdown or CSV (.csv) file, or - for standard input
+        file: String,
+        /// Key for the target space; picked interactively if left out
+        #[structopt(short, long)]
+        space: Option<String>,
+        /// Read the file as CSV, whatever its extension
+        #[structopt(long)]
+        csv: bool,
+        /// Status for work items that don't name one (default: the space's default
+        /// backlog or todo status)
+        #[structopt(long)]
+        status: Option<String>,
+        /// Only show what would be created
+        #[structopt(short = "n", long)]
+        dry_run: bool,
+    },
+
+    /// Apply a change to every work item a list query matches, or to the numbers read
+    /// from standard input with --stdin
+    Bulk {
+        #[structopt(flatten)]
+        query: list::Query,
+        /// Read newline-separated work item numbers from standard input instead of
+        /// querying
+        #[structopt(
+            long,
+            conflicts_with_all = &[
+                "space",
+                "all",
+                "backlog",
+                "status-type",
+                "exclude-status-type",
+                "limit",
+                "filter",
+            ]
+        )]
+        stdin: bool,
+        /// Don't ask for confirmation
+        #[structopt(short, long)]
+        yes: bool,
+        /// How many work items to change at the same time
+        #[structopt(short, long, default_value = "4")]
+        jobs: usize,
+        #[structopt(subcommand)]
+        action: bulk::Action,
+    },
+
+    /// Show a space's backlog as an impact/effort matrix
+    Matrix {
+        /// Key for the space; picked interactively if left out
+        space: Option<String>,
+        /// List the work items in each cell instead of counting them
+        #[structopt(short, long)]
+        list: bool,
+    },
+}
+
+/// Print a work item's details, code reviews and description
+fn print_item(
+    space_key: &str,
+    item: item_query::ItemQueryWorkItem,
+    reviews: &[(String, Option<review::CodeReview>)],
+) {
+    println!(
+        "{}-{}: {}",
+        space_key.bold(),
+        item.number.bold(),
+        item.title.bold()
+    );
+    println!("Status: {}", item.status.name.bold());
+    if let Some(effort) = &item.effort {
+        println!("Effort: {}", effort.bold());
+    }
+    if let Some(impact) = &item.impact {
+        println!("Impact: {}", impact.bold());
+    }
+
+    if !item.labels.is_empty() {
+        let mut labels = String::new();
+        for label in item.labels {
+            labels = format!("{:} {:}", labels, color::label(&label.name, &label.color));
+        }
+        println!("Labels:{}", labels);
+    }
+    if !item.members.is_empty() {
+        let members: Vec<&str> = item.members.iter().map(|m| m.username.as_str()).collect();
+        println!("Members: {}", members.join(", "));
+    }
+    if !item.watchers.is_empty() {
+        let watchers: Vec<&str> = item.watchers.iter().map(|w| w.username.as_str()).collect();
+        println!("Watchers: {}", watchers.join(", "));
+    }
+    review::print_for_item(reviews);
+    println!("\n{}\n", "Description:".bold());
+    termimad::print_text(item.description.as_str());
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Cli::from_args();
-    let client = reqwest::Client::new();
-
-    let color_mapping: HashMap<&str, &str> = [
-        ("gray", "#8D8D8D"),
-        ("mauve", "#8E8C99"),
-        ("slate", "#8B8D98"),
-        ("sage", "#B8BCBA"),
-        ("olive", "#898E87"),
-        ("sand", "#8D8D86"),
-        ("tomato", "#E54D2E"),
-        ("red", "#E5484D"),
-        ("ruby", "#E54666"),
-        ("crimson", "#E93D82"),
-        ("pink", "#D6409F"),
-        ("plum", "#AB4ABA"),
-        ("purple", "#8E4EC6"),
-        ("violet", "#6E56CF"),
-        ("iris", "#5B5BD6"),
-        ("indigo", "#3E63DD"),
-        ("blue", "#0090FF"),
-        ("cyan", "#00A2C7"),
-        ("teal", "#12A594"),
-        ("jade", "#29A383"),
-        ("green", "#30A46C"),
-        ("grass", "#46A758"),
-        ("bronze", "#A18072"),
-        ("gold", "#978365"),
-        ("brown", "#AD7F58"),
-        ("orange", "#F76B15"),
-        ("amber", "#FFC53D"),
-        ("yellow", "#FFE629"),
-        ("lime", "#BDEE63"),
-        ("mint", "#86EAD4"),
-        ("sky", "#7CE2FE"),
-    ]
-    .into();
+    // The completion scripts' helper, `km __complete -- WORD...`, is answered before
+    // parsing, so the half-typed words can't trip up the parser
+    let mut raw_args = std::env::args().skip(1).peekable();
+    if raw_args.next_if(|a| a == "__complete").is_some() {
+        raw_args.next_if(|a| a == "--");
+        for candidate in complete::candidates(&raw_args.collect::<Vec<String>>(), &Cli::clap()) {
+            println!("{:}", candidate);
+        }
+        return Ok(());
+    }
 
-    match args.cmd {
-        Commands::Organization => {
-            let q = SpaceQuery::build_query(space_query::Variables {});
+    let args = Cli::from_args();
 
-            let res = client
-                .post("https://toil.kitemaker.co/developers/graphql")
-                .bearer_auth(args.token)
-                .json(&q)
-                .send()
-                .await?;
+    // Completion has to work without a token, a config or the network
+    if let Commands::Completions { shell } = &args.cmd {
+        print!("{:}", complete::script(shell, Cli::clap())?);
+        return Ok(());
+    }
 
-            res.error_for_status_ref()?;
+    // The commit-msg hook falls back to the cache rather than failing without a token
+    let offline_ok = matches!(
+        &args.cmd,
+        Commands::Git(GitSubCommands {
+            cmd: GitCommand::CheckMessage { .. }
+        })
+    );
+    let token = match (args.token, &args.replay) {
+        (Some(token), _) => token,
+        (None, Some(_)) => String::new(),
+        (None, None) if offline_ok => String::new(),
+        (None, None) => return Err(anyhow!("Missing token, set --token or $KM_TOKEN").into()),
+    };
+    let mut config = Config::load()?;
+    let api = Api::new(
+        token,
+        api::Options {
+            endpoint: args.endpoint.or(config.endpoint.clone()),
+            timeout: Duration::from_secs(
+                args.timeout
+                    .or(config.timeout_seconds)
+                    .unwrap_or(api::DEFAULT_TIMEOUT),
+            ),
+            retries: args
+                .retries
+                .or(config.retries)
+                .unwrap_or(api::DEFAULT_RETRIES),
+            retry_mutations: args.retry_mutations || config.retry_mutations.unwrap_or(false),
+            proxy: config::env_or("KM_PROXY", config.proxy.clone()),
+            no_proxy: config::env_or("KM_NO_PROXY", config.no_proxy.clone()),
+            ca_certs: match std::env::var_os("KM_CA_CERTS").filter(|v| !v.is_empty()) {
+                Some(paths) => std::env::split_paths(&paths).collect(),
+                None => config.ca_certs.iter().map(PathBuf::from).collect(),
+            },
+            client_cert: config::env_or("KM_CLIENT_CERT", config.client_cert.clone())
+                .map(PathBuf::from),
+            client_key: config::env_or("KM_CLIENT_KEY", config.client_key.clone())
+                .map(PathBuf::from),
+            client_cert_password: config::env_or(
+                "KM_CLIENT_CERT_PASSWORD",
+                config.client_cert_password.clone(),
+            ),
+            user_agent: config::env_or("KM_USER_AGENT", config.user_agent.clone()),
+            log_level: trace::Level::from_count(args.verbose)
+                .max(args.log.unwrap_or(trace::Level::Off)),
+            trace_file: args.trace_file,
+            fixtures: match (args.record, args.replay) {
+                (Some(dir), _) => Some(fixture::Mode::Record(dir)),
+                (None, Some(dir)) => Some(fixture::Mode::Replay(dir)),
+                (None, None) => None,
+            },
+        },
+    )?;
 
-            let response_json: Response<space_query::ResponseData> = res.json().await?;
-            let response_data: space_query::SpaceQueryOrganization =
-                response_json.data.unwrap().organization;
+    match args.cmd {
+        Commands::Organization => {
+            let response_data = resolve::organization(&api).await?;
 
             println!(
                 "{:} {:}\n",
@@ -174,11 +675,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!(
                     "{:<15}{:<25}{:}",
                     user.username.yellow(),
-                    if user.name.is_some() {
-                        user.name.as_ref().unwrap()
-                    } else {
-                        ""
-                    },
+                    user.name.as_deref().unwrap_or(""),
                     if user.guest { "yes" } else { "" }
                 );
             }
@@ -192,119 +689,303 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         }
 
-        Commands::Item(arg) => {
-            match arg.cmd {
-                Item::List {
-                    space,
-                    all,
-                    backlog,
-                } => {
-                    let q = SpaceQuery::build_query(space_query::Variables {});
-
-                    let res = client
-                        .post("https://toil.kitemaker.co/developers/graphql")
-                        .bearer_auth(args.token.to_string())
-                        .json(&q)
-                        .send()
-                        .await?;
-
-                    res.error_for_status_ref()?;
+        Commands::Board { space } => {
+            let org = resolve::organization(&api).await?;
+            let spc = resolve::space_or_pick(&org, space.as_deref())?;
+            board::run(&api, &org, spc).await?;
+        }
 
-                    let response_json: Response<space_query::ResponseData> = res.json().await?;
-                    let response_data = response_json.data.unwrap();
+        Commands::Schema(schema_command) => match schema_command.cmd {
+            SchemaCommand::Check { file } => {
+                let sdl = match &file {
+                    Some(path) => std::fs::read_to_string(path)
+                        .map_err(|e| anyhow!("Could not read {:}: {:}", path, e))?,
+                    None => introspect::fetch_sdl(&api).await?,
+                };
+                let live = schema::Schema::parse(&sdl)?;
+                let changes = schema::diff(&schema::Schema::bundled(), &live);
+                if changes.is_empty() {
+                    println!("The bundled schema is up to date");
+                } else {
+                    schema::print_changes(&changes);
+                    let breaking = changes.iter().filter(|c| c.breaking()).count();
+                    if breaking > 0 {
+                        Err(anyhow!(
+                            "{:} of {:} changes remove or change something the bundled schema has",
+                            breaking,
+                            changes.len()
+                        ))?;
+                    }
+                }
+            }
+            SchemaCommand::Dump { output } => {
+                let sdl = introspect::fetch_sdl(&api).await?;
+                match output {
+                    Some(path) => {
+                        std::fs::write(&path, sdl)
+                            .map_err(|e| anyhow!("Could not write {:}: {:}", path, e))?;
+                        println!("Wrote the schema to {:}", path.bold());
+                    }
+                    None => print!("{:}", sdl),
+                }
+            }
+        },
+
+        Commands::Git(GitSubCommands { cmd }) => match cmd {
+            GitCommand::Branch {
+                number,
+                pattern,
+                start,
+                no_start,
+            } => {
+                let org = resolve::organization(&api).await?;
+                let number = resolve::item_number(&api, &org, number).await?;
+                let item = resolve::item(&api, &number).await?;
+                let number = format!("{:}-{:}", item.space.key, item.number);
+                let pattern = pattern
+                    .or(config.branch_pattern.clone())
+                    .unwrap_or_else(|| git::DEFAULT_BRANCH_PATTERN.to_string());
+                let branch = git::branch_name(&pattern, &item.space.key, &item.number, &item.title);
+
+                let created = git::checkout(&branch)?;
+                if created {
+                    println!("Created and checked out {:}", branch.bold());
+                } else {
+                    println!("Checked out existing branch {:}", branch.bold());
+                }
 
-                    for spc in response_data.organization.spaces.iter() {
-                        let mut print_items = false;
+                // Going back to an existing branch leaves the work item where it is
+                let start = start || (!no_start && config.start_on_branch.unwrap_or(false));
+                if created
+                    && start
+                    && item.status.type_ != api::item_by_number_query::StatusType::IN_PROGRESS
+                {
+                    let spc = resolve::space(&org, &item.space.key)?;
+                    let target = resolve::in_progress_status(spc)?;
+                    api.query::<MoveWorkItem>(move_work_item::Variables {
+                        id: item.id,
+                        status_id: target.id.clone(),
+                    })
+                    .await?;
+                    println!(
+                        "Moved {:} from {:} to {:}",
+                        number.bold(),
+                        item.status.name,
+                        target.name.bold()
+                    );
+                }
+            }
+            GitCommand::InstallHook { prepend, force } => {
+                let path = git::install_hook(prepend, force)?;
+                println!("Installed the commit-msg hook in {:}", path.display());
+            }
+            GitCommand::CheckMessage { file, prepend } => {
+                git::check_message(&api, &file, prepend).await?;
+            }
+            GitCommand::Current { number } => {
+                let branch = git::current_branch()?;
+                let org = resolve::organization(&api).await?;
+                let found = git::number_in(&org, &branch)
+                    .ok_or_else(|| anyhow!("No work item number in branch {:}", branch))?;
+                if number {
+                    println!("{:}", found);
+                } else {
+                    let item = resolve::item(&api, &found).await?;
+                    println!(
+                        "{:} {:} {:}",
+                        found.yellow().bold(),
+                        item.title.bold(),
+                        format!("({:})", item.status.name).dimmed()
+                    );
+                }
+            }
+        },
+
+        Commands::Changelog {
+            since,
+            until,
+            from_tag,
+            to_tag,
+            space,
+            label,
+            filter,
+            group_by,
+            template,
+            jobs,
+        } => {
+            let (since, until) = changelog::range(
+                since.as_deref(),
+                until.as_deref(),
+                from_tag.as_deref(),
+                to_tag.as_deref(),
+            )?;
+            let heading = changelog::heading(since, until, to_tag.as_deref());
+            let template = changelog::template(template.as_deref(), group_by)?;
+
+            let org = resolve::organization(&api).await?;
+            if let Some(key) = &space {
+                resolve::space(&org, key)?;
+            }
+            let query = list::Query {
+                space,
+                all: false,
+                backlog: false,
+                // Items archived since they were closed still belong in the release notes
+                status_type: vec![
+                    items_query::StatusType::DONE,
+                    items_query::StatusType::ARCHIVED,
+                ],
+                exclude_status_type: Vec::new(),
+                limit: None,
+                filter,
+            };
+            let rows = query.rows(&api, &org, &config, jobs).await?;
+            let rows = changelog::closed_between(rows, since, until, label.as_deref());
+            print!(
+                "{:}",
+                changelog::render(&template, &heading, since, until, &rows, group_by)?
+            );
+        }
 
-                        match space {
-                            None => {
-                                println!("\n\n{:} {:}", "Space:".bold(), spc.name.bold());
-                                print_items = true;
-                            }
-                            Some(ref x) => {
-                                if x == &spc.key.to_string() {
-                                    print_items = true;
-                                }
-                            }
-                        }
+        Commands::Review(ReviewSubCommands { cmd }) => match cmd {
+            ReviewCommand::Show { url } => {
+                review::print(&review::find(&api, &url).await?);
+            }
+            ReviewCommand::Links { url, add, remove } => {
+                let found = review::find(&api, &url).await?;
+                if add.is_empty() && remove.is_empty() {
+                    review::print(&found);
+                } else {
+                    review::edit_links(&api, &found, &add, &remove).await?;
+                    println!(
+                        "Links of {:} updated",
+                        format!("#{:}", found.details.number).bold()
+                    );
+                }
+            }
+        },
+
+        // Answered before the API is set up
+        Commands::Completions { .. } => {}
+
+        Commands::Api {
+            query,
+            fields,
+            raw_fields,
+            operation,
+            validate,
+        } => {
+            raw::run(&api, &query, &fields, &raw_fields, operation, validate).await?;
+        }
 
-                        if print_items {
-                            println!(
-                                "{:<30}{:<20}{:<40}",
-                                "Status".bold().underline(),
-                                "Key".bold().underline(),
-                                "Title".bold().underline()
-                            );
+        Commands::Search {
+            query,
+            space,
+            comments,
+            archived,
+            limit,
+            refresh,
+        } => {
+            let max_age = if refresh {
+                Some(Duration::ZERO)
+            } else {
+                Some(Duration::from_secs(60 * config.cache_minutes.unwrap_or(15)))
+            };
+            let terms = search::terms(&query);
+
+            let org = resolve::organization(&api).await?;
+
+            let mut hits = Vec::new();
+            for spc in org.spaces.iter() {
+                if space
+                    .as_ref()
+                    .is_some_and(|x| !x.eq_ignore_ascii_case(&spc.key))
+                {
+                    continue;
+                }
+                let documents =
+                    search::documents(&api, &spc.id, &spc.key, comments, max_age).await?;
+                for doc in documents {
+                    if doc.archived && !archived {
+                        continue;
+                    }
+                    if let Some(score) = search::score(&doc, &terms) {
+                        hits.push((score, spc.key.clone(), doc));
+                    }
+                }
+            }
 
-                            let mut has_more = true;
-                            let mut cursor: Option<String> = None;
+            hits.sort_by(|a, b| b.0.cmp(&a.0).then(b.2.updated_at.cmp(&a.2.updated_at)));
 
-                            while has_more {
-                                let q = ItemsQuery::build_query(items_query::Variables {
-                                    space_id: spc.id.to_string(),
-                                    cursor: cursor,
-                                });
+            println!(
+                "{:}\n",
+                format!("{:} matches", hits.len()).bold().underline()
+            );
+            for (_, key, doc) in hits.iter().take(limit) {
+                println!(
+                    "{:<12}{:<20}{:}",
+                    format!("{:}-{:}", key, doc.number).yellow(),
+                    doc.status,
+                    search::highlight(&doc.title, &terms)
+                );
+                if let Some(snippet) = search::snippet(doc, &terms) {
+                    println!("{:<12}{:}", "", snippet.dimmed());
+                }
+            }
+        }
 
-                                let res = client
-                                    .post("https://toil.kitemaker.co/developers/graphql")
-                                    .bearer_auth(args.token.to_string())
-                                    .json(&q)
-                                    .send()
-                                    .await?;
+        Commands::Item(arg) => {
+            match arg.cmd {
+                Item::List {
+                    query,
+                    save_filter,
+                    sort,
+                    group_by,
+                    columns,
+                    output,
+                    jobs,
+                } => {
+                    if let (Some(name), Some(text)) = (save_filter, &query.filter) {
+                        Filter::parse(text, &config)?;
+                        config.save_filter(&name, text)?;
+                        println!("Saved filter @{:}", name.bold());
+                    }
 
-                                res.error_for_status_ref()?;
-
-                                let response_json: Response<items_query::ResponseData> =
-                                    res.json().await?;
-                                let response_data = response_json.data.unwrap();
-
-                                has_more = response_data.work_items.has_more;
-                                cursor = Some(response_data.work_items.cursor.to_string());
-
-                                for item in response_data.work_items.work_items {
-                                    if (all
-                                        && item.status.type_ != items_query::StatusType::ARCHIVED)
-                                        || (backlog
-                                            && item.status.type_
-                                                == items_query::StatusType::BACKLOG)
-                                        || (!backlog
-                                            && !all
-                                            && (item.status.type_ == items_query::StatusType::TODO
-                                                || item.status.type_
-                                                    == items_query::StatusType::IN_PROGRESS))
-                                    {
-                                        let mut labels = format!("");
-                                        for label in item.labels {
-                                            let hex_color = if label.color.starts_with('#') {
-                                                label.color.clone()
-                                            } else {
-                                                color_mapping
-                                                    .get(&label.color.as_str())
-                                                    .unwrap_or(&"#FFFFFF")
-                                                    .to_string()
-                                            };
-                                            let rgb = Rgb::from_hex_str(&hex_color).unwrap();
-                                            labels = format!(
-                                                "{:} {:}",
-                                                labels,
-                                                label.name.truecolor(
-                                                    rgb.get_red() as u8,
-                                                    rgb.get_green() as u8,
-                                                    rgb.get_blue() as u8
-                                                )
-                                            );
-                                        }
-
-                                        println!(
-                                            "{:<30}{:<20}{:} {:}",
-                                            item.status.name,
-                                            spc.key.to_string() + "-" + &item.number.to_string(),
-                                            item.title,
-                                            labels.italic()
-                                        );
-                                    }
+                    let org = resolve::organization(&api).await?;
+                    let group_by = group_by.unwrap_or(if query.space.is_none() {
+                        list::GroupBy::Space
+                    } else {
+                        list::GroupBy::None
+                    });
+                    let columns = match (columns.is_empty(), output) {
+                        (false, _) => columns,
+                        (true, list::Output::Table) => list::Column::defaults(),
+                        (true, list::Output::Tsv) => list::Column::tsv_defaults(),
+                    };
+
+                    // Space groups don't depend on each other, so print each one as soon
+                    // as it's in rather than waiting for the slowest space
+                    if output == list::Output::Table && group_by == list::GroupBy::Space {
+                        let mut first = true;
+                        query
+                            .rows_by_space(&api, &org, &config, jobs, |mut rows| {
+                                if rows.is_empty() {
+                                    return;
                                 }
-                            }
+                                if !first {
+                                    println!();
+                                }
+                                first = false;
+                                list::sort(&mut rows, &sort);
+                                list::print(&rows, &columns, group_by);
+                            })
+                            .await?;
+                    } else {
+                        let mut rows = query.rows(&api, &org, &config, jobs).await?;
+                        list::sort(&mut rows, &sort);
+                        match output {
+                            list::Output::Table => list::print(&rows, &columns, group_by),
+                            list::Output::Tsv => list::print_tsv(&rows, &columns),
                         }
                     }
                 }
@@ -312,197 +993,448 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     space,
                     title,
                     description,
+                    status,
+                    label,
+                    member,
+                    watcher,
+                    position,
+                    sort,
+                    effort,
+                    impact,
+                    template,
                 } => {
-                    let q = SpaceQuery::build_query(space_query::Variables {});
-
-                    let res = client
-                        .post("https://toil.kitemaker.co/developers/graphql")
-                        .bearer_auth(args.token.to_string())
-                        .json(&q)
-                        .send()
-                        .await?;
-
-                    res.error_for_status_ref()?;
+                    let org = resolve::organization(&api).await?;
 
-                    let response_json: Response<space_query::ResponseData> = res.json().await?;
-                    let response_data = response_json.data.unwrap();
-
-                    let spc = response_data
-                        .organization
-                        .spaces
+                    // A single argument is the space if it names one, and the title otherwise
+                    let (space, title) = match (space, title) {
+                        (Some(only), None) if resolve::space(&org, &only).is_err() => {
+                            (None, Some(only))
+                        }
+                        other => other,
+                    };
+                    let spc = resolve::space_or_pick(&org, space.as_deref())?;
+
+                    let (title, description, status, label, member, watcher, effort, impact) =
+                        match template {
+                            None => (
+                                title,
+                                description,
+                                status,
+                                label,
+                                member,
+                                watcher,
+                                effort,
+                                impact,
+                            ),
+                            Some(name) => {
+                                let mut template = template::load(&name)?;
+                                if title.is_some() {
+                                    template.front.title = None;
+                                }
+                                template.fill()?;
+                                let front = template.front;
+                                (
+                                    title.or(front.title),
+                                    description.or(Some(template.body)),
+                                    status.or(front.status),
+                                    [front.labels, label].concat(),
+                                    [front.members, member].concat(),
+                                    [front.watchers, watcher].concat(),
+                                    effort.or(front.effort),
+                                    impact.or(front.impact),
+                                )
+                            }
+                        };
+                    let title = match title {
+                        Some(title) => title,
+                        None => picker::prompt("Title")?.ok_or_else(|| anyhow!("Missing title"))?,
+                    };
+
+                    let status = match status {
+                        Some(name) => resolve::status(spc, Some(&name))?,
+                        None => resolve::default_status(spc)?,
+                    };
+                    let mut label_ids = label
+                        .iter()
+                        .map(|name| resolve::label(spc, Some(name)).map(|l| l.id.clone()))
+                        .collect::<anyhow::Result<Vec<String>>>()?;
+                    let mut member_ids = member
                         .iter()
-                        .find(|&s| s.key == space);
+                        .map(|name| resolve::user(&org, &config, Some(name)).map(|u| u.id.clone()))
+                        .collect::<anyhow::Result<Vec<String>>>()?;
+                    let mut watcher_ids = watcher
+                        .iter()
+                        .map(|name| resolve::user(&org, &config, Some(name)).map(|u| u.id.clone()))
+                        .collect::<anyhow::Result<Vec<String>>>()?;
+                    // A template and the flags may name the same ones
+                    for ids in [&mut label_ids, &mut member_ids, &mut watcher_ids] {
+                        ids.sort();
+                        ids.dedup();
+                    }
 
-                    match spc {
-                        None => {
-                            println!("Could not find space {:}", space);
+                    // Leaving out the sort puts the work item at the top
+                    let sort = match (sort, position) {
+                        (Some(sort), _) => Some(sort),
+                        (None, rank::Position::Top) => None,
+                        (None, rank::Position::Bottom) => {
+                            let column =
+                                rank::column(&api, &spc.id, &status.id, (&status.type_).into())
+                                    .await?;
+                            Some(rank::between(column.last().map(|i| i.sort.as_str()), None)?)
                         }
-                        Some(s) => {
-                            // Find the default status
-                            let default_status = s
-                                .statuses
-                                .iter()
-                                .find(|&st| {
-                                    st.default == true
-                                        && (st.type_ == space_query::StatusType::BACKLOG
-                                            || st.type_ == space_query::StatusType::TODO)
-                                })
-                                .expect("missing default open status");
-
-                            let q = CreateWorkItem::build_query(create_work_item::Variables {
-                                status_id: default_status.id.to_string(),
-                                title: title,
-                                description: description,
-                            });
-
-                            let res = client
-                                .post("https://toil.kitemaker.co/developers/graphql")
-                                .bearer_auth(args.token.to_string())
-                                .json(&q)
-                                .send()
-                                .await?;
+                    };
+
+                    let response_data = api
+                        .query::<CreateWorkItem>(create_work_item::Variables {
+                            status_id: status.id.clone(),
+                            title,
+                            description,
+                            sort,
+                            member_ids: Some(member_ids),
+                            watcher_ids: Some(watcher_ids),
+                            label_ids: Some(label_ids),
+                            effort,
+                            impact,
+                        })
+                        .await?;
 
-                            res.error_for_status_ref()?;
+                    let item = api
+                        .query::<ItemQuery>(item_query::Variables {
+                            item_id: response_data.create_work_item.work_item.id,
+                        })
+                        .await?
+                        .work_item;
+                    println!(
+                        "Work item {:} created\n",
+                        format!("{:}-{:}", spc.key, item.number).bold()
+                    );
+                    print_item(&spc.key, item, &[]);
+                }
+                Item::Edit {
+                    numbers,
+                    title,
+                    description,
+                    effort,
+                    impact,
+                } => {
+                    let org = resolve::organization(&api).await?;
+                    let numbers = resolve::item_numbers(&api, &org, numbers).await?;
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for number in numbers {
+                        let result: anyhow::Result<()> = async {
+                            let item = resolve::item(&api, &number).await?;
+
+                            api.query::<EditWorkItem>(edit_work_item::Variables {
+                                id: item.id,
+                                title: title.clone(),
+                                description: description.clone(),
+                                effort: effort.clone(),
+                                impact: impact.clone(),
+                            })
+                            .await?;
+                            println!("Work item {:} updated", number.bold());
+                            Ok(())
+                        }
+                        .await;
+                        tally.record(&number, result);
+                    }
+                    tally.finish()?;
+                }
+                Item::View { numbers } => {
+                    let org = resolve::organization(&api).await?;
+                    let numbers = resolve::item_numbers(&api, &org, numbers).await?;
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for (i, number) in numbers.iter().enumerate() {
+                        if i > 0 {
+                            println!("\n");
+                        }
+                        let result: anyhow::Result<()> = async {
+                            let found = resolve::item(&api, number).await?;
 
-                            let response_json: Response<create_work_item::ResponseData> =
-                                res.json().await?;
-                            let response_data = response_json.data.unwrap();
+                            let response_data = api
+                                .query::<ItemQuery>(item_query::Variables { item_id: found.id })
+                                .await?;
 
-                            let work_item_number = format!(
-                                "{:}-{:}",
-                                s.key, response_data.create_work_item.work_item.number
+                            let reviews =
+                                review::for_item(&api, &response_data.work_item.activities).await;
+                            print_item(&found.space.key, response_data.work_item, &reviews);
+                            Ok(())
+                        }
+                        .await;
+                        tally.record(number, result);
+                    }
+                    tally.finish()?;
+                }
+                Item::Move { args } => {
+                    let org = resolve::organization(&api).await?;
+                    let (numbers, mut status) =
+                        resolve::item_numbers_and(&api, &org, args, "status").await?;
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for number in numbers {
+                        let result: anyhow::Result<()> = async {
+                            let item = resolve::item(&api, &number).await?;
+                            let spc = resolve::space(&org, &item.space.key)?;
+                            let target = resolve::status(spc, status.as_deref())?;
+                            // Move the rest to a picked status too, rather than asking again
+                            status = Some(target.name.clone());
+
+                            api.query::<MoveWorkItem>(move_work_item::Variables {
+                                id: item.id,
+                                status_id: target.id.clone(),
+                            })
+                            .await?;
+                            println!(
+                                "Moved {:} from {:} to {:}",
+                                number.bold(),
+                                item.status.name,
+                                target.name.bold()
                             );
-                            println!("Work item {:} created", work_item_number.bold());
+                            Ok(())
                         }
+                        .await;
+                        tally.record(&number, result);
                     }
+                    tally.finish()?;
                 }
-                Item::View { number } => {
-                    let mut parts = number.split("-");
-                    let space = parts.next().expect("Missing space key");
-                    let number = parts.next().expect("Missing work item number");
-
-                    // First find the correct space and id
-                    let q = SpaceQuery::build_query(space_query::Variables {});
-
-                    let res = client
-                        .post("https://toil.kitemaker.co/developers/graphql")
-                        .bearer_auth(args.token.to_string())
-                        .json(&q)
-                        .send()
-                        .await?;
-
-                    res.error_for_status_ref()?;
-
-                    let response_json: Response<space_query::ResponseData> = res.json().await?;
-                    let response_data = response_json.data.unwrap();
-
-                    let spc = response_data
-                        .organization
-                        .spaces
-                        .iter()
-                        .find(|&s| s.key == space);
-
-                    match spc {
+                Item::Rank {
+                    number,
+                    before,
+                    after,
+                    top,
+                    bottom,
+                } => {
+                    let org = resolve::organization(&api).await?;
+                    let number = resolve::item_number(&api, &org, number).await?;
+                    let item = resolve::item(&api, &number).await?;
+
+                    // The column we're ranking in is the anchor's, or the item's own
+                    let anchor = match before.as_ref().or(after.as_ref()) {
+                        Some(anchor) => Some(resolve::item(&api, anchor).await?),
+                        None => None,
+                    };
+                    let (space_id, status) = match &anchor {
+                        Some(a) => (&a.space.id, &a.status),
+                        None => (&item.space.id, &item.status),
+                    };
+                    let column: Vec<api::WorkItem> =
+                        rank::column(&api, space_id, &status.id, (&status.type_).into())
+                            .await?
+                            .into_iter()
+                            .filter(|i| i.id != item.id)
+                            .collect();
+
+                    let neighbours = match &anchor {
+                        Some(a) => {
+                            let index = column
+                                .iter()
+                                .position(|i| i.id == a.id)
+                                .ok_or_else(|| anyhow!("{:} is the item being ranked", a.number))?;
+                            if before.is_some() {
+                                (index.checked_sub(1).map(|i| &column[i]), column.get(index))
+                            } else {
+                                (column.get(index), column.get(index + 1))
+                            }
+                        }
+                        None if top => (None, column.first()),
                         None => {
-                            println!("Could not find space {:}", space);
+                            debug_assert!(bottom);
+                            (column.last(), None)
                         }
-                        Some(s) => {
-                            // Find the work item
-                            let mut has_more = true;
-                            let mut cursor: Option<String> = None;
-
-                            while has_more {
-                                let q = ItemsQuery::build_query(items_query::Variables {
-                                    space_id: s.id.to_string(),
-                                    cursor: cursor,
-                                });
-
-                                let res = client
-                                    .post("https://toil.kitemaker.co/developers/graphql")
-                                    .bearer_auth(args.token.to_string())
-                                    .json(&q)
-                                    .send()
-                                    .await?;
-
-                                res.error_for_status_ref()?;
-
-                                let response_json: Response<items_query::ResponseData> =
-                                    res.json().await?;
-                                let response_data = response_json.data.unwrap();
-
-                                has_more = response_data.work_items.has_more;
-                                cursor = Some(response_data.work_items.cursor.to_string());
-
-                                let item = response_data
-                                    .work_items
-                                    .work_items
-                                    .iter()
-                                    .find(|&w| w.number == number);
-
-                                match item {
-                                    None => {}
-                                    Some(i) => {
-                                        let q = ItemQuery::build_query(item_query::Variables {
-                                            item_id: i.id.to_string(),
-                                        });
-
-                                        let res = client
-                                            .post("https://toil.kitemaker.co/developers/graphql")
-                                            .bearer_auth(args.token.to_string())
-                                            .json(&q)
-                                            .send()
-                                            .await?;
-
-                                        res.error_for_status_ref()?;
-
-                                        let response_json: Response<item_query::ResponseData> =
-                                            res.json().await?;
-                                        let response_data = response_json.data.unwrap();
-
-                                        let item = response_data.work_item;
-
-                                        println!(
-                                            "{}-{}: {}",
-                                            s.key.bold(),
-                                            item.number.bold(),
-                                            item.title.bold()
-                                        );
-                                        println!("Status: {}", item.status.name.bold());
-
-                                        if item.labels.len() > 0 {
-                                            let mut labels = format!("");
-                                            for label in item.labels {
-                                                let hex_color = if label.color.starts_with('#') {
-                                                    label.color.clone()
-                                                } else {
-                                                    color_mapping
-                                                        .get(&label.color.as_str())
-                                                        .unwrap_or(&"#FFFFFF")
-                                                        .to_string()
-                                                };
-                                                let rgb = Rgb::from_hex_str(&hex_color).unwrap();
-                                                labels = format!(
-                                                    "{:} {:}",
-                                                    labels,
-                                                    label.name.truecolor(
-                                                        rgb.get_red() as u8,
-                                                        rgb.get_green() as u8,
-                                                        rgb.get_blue() as u8
-                                                    ),
-                                                );
-                                            }
-                                            println!("{}{}", "Labels:", labels);
-                                        }
-                                        println!("\n{}\n", "Description:".bold());
-                                        termimad::print_text(item.description.as_str());
-
-                                        // We're done, so skip searching for more items
-                                        return Ok(());
-                                    }
-                                }
+                    };
+                    let sort = rank::between(
+                        neighbours.0.map(|i| i.sort.as_str()),
+                        neighbours.1.map(|i| i.sort.as_str()),
+                    )?;
+
+                    api.query::<RankWorkItem>(rank_work_item::Variables {
+                        id: item.id.clone(),
+                        status_id: status.id.clone(),
+                        sort,
+                    })
+                    .await?;
+
+                    let place = match (&before, &after) {
+                        (Some(b), _) => format!("above {:}", b),
+                        (_, Some(a)) => format!("below {:}", a),
+                        _ if top => format!("at the top of {:}", status.name),
+                        _ => format!("at the bottom of {:}", status.name),
+                    };
+                    println!("Ranked {:} {:}", number.bold(), place);
+                }
+                Item::Assign { args, remove } => {
+                    let org = resolve::organization(&api).await?;
+                    let (numbers, mut user) =
+                        resolve::item_numbers_and(&api, &org, args, "user").await?;
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for number in numbers {
+                        let result: anyhow::Result<()> = async {
+                            let item = resolve::item(&api, &number).await?;
+                            let member = resolve::user(&org, &config, user.as_deref())?;
+                            user = Some(member.username.clone());
+
+                            if remove {
+                                api.query::<RemoveMembersFromWorkItem>(
+                                    remove_members_from_work_item::Variables {
+                                        id: item.id,
+                                        member_ids: vec![member.id.clone()],
+                                    },
+                                )
+                                .await?;
+                                println!(
+                                    "Removed {:} from {:}",
+                                    member.username.bold(),
+                                    number.bold()
+                                );
+                            } else {
+                                api.query::<AddMembersToWorkItem>(
+                                    add_members_to_work_item::Variables {
+                                        id: item.id,
+                                        member_ids: vec![member.id.clone()],
+                                    },
+                                )
+                                .await?;
+                                println!(
+                                    "Assigned {:} to {:}",
+                                    member.username.bold(),
+                                    number.bold()
+                                );
+                            }
+                            Ok(())
+                        }
+                        .await;
+                        tally.record(&number, result);
+                    }
+                    tally.finish()?;
+                }
+                Item::Label { args, remove } => {
+                    let org = resolve::organization(&api).await?;
+                    let (numbers, mut label) =
+                        resolve::item_numbers_and(&api, &org, args, "label").await?;
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for number in numbers {
+                        let result: anyhow::Result<()> = async {
+                            let item = resolve::item(&api, &number).await?;
+                            let spc = resolve::space(&org, &item.space.key)?;
+                            let found = resolve::label(spc, label.as_deref())?;
+                            label = Some(found.name.clone());
+
+                            if remove {
+                                api.query::<RemoveLabelsFromWorkItem>(
+                                    remove_labels_from_work_item::Variables {
+                                        id: item.id,
+                                        label_ids: vec![found.id.clone()],
+                                    },
+                                )
+                                .await?;
+                                println!(
+                                    "Removed label {:} from {:}",
+                                    color::label(&found.name, &found.color),
+                                    number.bold()
+                                );
+                            } else {
+                                api.query::<AddLabelsToWorkItem>(
+                                    add_labels_to_work_item::Variables {
+                                        id: item.id,
+                                        label_ids: vec![found.id.clone()],
+                                    },
+                                )
+                                .await?;
+                                println!(
+                                    "Added label {:} to {:}",
+                                    color::label(&found.name, &found.color),
+                                    number.bold()
+                                );
                             }
+                            Ok(())
                         }
+                        .await;
+                        tally.record(&number, result);
                     }
+                    tally.finish()?;
+                }
+                Item::Comment { args } => {
+                    let org = resolve::organization(&api).await?;
+                    let (numbers, body) =
+                        resolve::item_numbers_and(&api, &org, args, "comment").await?;
+                    let body = match body {
+                        Some(body) => body,
+                        None => {
+                            picker::prompt("Comment")?.ok_or_else(|| anyhow!("Missing comment"))?
+                        }
+                    };
+                    let mut tally = bulk::Tally::new(numbers.len());
+                    for number in numbers {
+                        let result: anyhow::Result<()> = async {
+                            let item = resolve::item(&api, &number).await?;
+                            api.query::<CommentOnWorkItem>(comment_on_work_item::Variables {
+                                id: item.id,
+                                body: body.clone(),
+                            })
+                            .await?;
+                            println!("Commented on {:}", number.bold());
+                            Ok(())
+                        }
+                        .await;
+                        tally.record(&number, result);
+                    }
+                    tally.finish()?;
+                }
+                Item::Import {
+                    file,
+                    space,
+                    csv,
+                    status,
+                    dry_run,
+                } => {
+                    let text = if file == "-" {
+                        std::io::read_to_string(std::io::stdin())?
+                    } else {
+                        std::fs::read_to_string(&file)
+                            .map_err(|e| anyhow!("Could not read {:}: {:}", file, e))?
+                    };
+                    let drafts = if csv || file.to_lowercase().ends_with(".csv") {
+                        import::parse_csv(&text)?
+                    } else {
+                        import::parse_markdown(&text)
+                    };
+
+                    let org = resolve::organization(&api).await?;
+                    let spc = resolve::space_or_pick(&org, space.as_deref())?;
+                    let default_status = match status {
+                        Some(name) => resolve::status(spc, Some(&name))?,
+                        None => resolve::default_status(spc)?,
+                    };
+                    import::run(&api, &org, spc, &config, default_status, drafts, dry_run).await?;
+                }
+                Item::Bulk {
+                    query,
+                    stdin,
+                    yes,
+                    jobs,
+                    action,
+                } => {
+                    let org = resolve::organization(&api).await?;
+                    let targets = if stdin {
+                        bulk::targets(&api, resolve::read_numbers()?, jobs).await?
+                    } else {
+                        let mut rows = query.rows(&api, &org, &config, jobs).await?;
+                        list::sort(&mut rows, &[]);
+                        rows.into_iter().map(bulk::Target::from).collect()
+                    };
+                    bulk::run(&api, &org, &config, targets, &action, yes, jobs).await?;
+                }
+                Item::Matrix { space, list } => {
+                    let org = resolve::organization(&api).await?;
+                    let spc = resolve::space_or_pick(&org, space.as_deref())?;
+                    let status_filter = items_query::StatusFilter {
+                        type_: items_query::StatusFilterType::INCLUDE,
+                        filter: vec![
+                            items_query::StatusType::BACKLOG,
+                            items_query::StatusType::TODO,
+                        ],
+                    };
+                    let items = api.work_items(&spc.id, Some(status_filter), None).await?;
+                    matrix::print(&spc.key, items, list);
                 }
             }
         }
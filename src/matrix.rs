@@ -0,0 +1,118 @@
+//! The impact/effort matrix for `km item matrix`, highest impact at the top and lowest
+//! effort on the left so the quick wins end up in the top left corner
+
+use colored::*;
+
+use crate::api::WorkItem;
+use crate::list::size_rank;
+
+/// Shown for work items without an effort or impact
+const UNSET: &str = "-";
+
+/// The distinct values of a field, smallest first with unset last
+fn values(items: &[WorkItem], field: fn(&WorkItem) -> &Option<String>) -> Vec<Option<String>> {
+    let mut values: Vec<Option<String>> = items.iter().map(|i| field(i).clone()).collect();
+    values.sort_by_key(size_rank);
+    values.dedup_by_key(|v| size_rank(v));
+    values
+}
+
+fn same(a: &Option<String>, b: &Option<String>) -> bool {
+    size_rank(a) == size_rank(b)
+}
+
+fn name(value: &Option<String>) -> String {
+    value.as_deref().unwrap_or(UNSET).to_uppercase()
+}
+
+pub fn print(space_key: &str, items: Vec<WorkItem>, list: bool) {
+    if items.is_empty() {
+        println!("No work items in the backlog of {:}", space_key);
+        return;
+    }
+
+    let efforts = values(&items, |i| &i.effort);
+    let mut impacts = values(&items, |i| &i.impact);
+    // Highest impact first, but keep unset at the bottom
+    let unset = impacts.iter().position(|v| v.is_none());
+    let end = unset.unwrap_or(impacts.len());
+    impacts[..end].reverse();
+
+    let cell = |impact: &Option<String>, effort: &Option<String>| -> Vec<&WorkItem> {
+        let mut cell: Vec<&WorkItem> = items
+            .iter()
+            .filter(|i| same(&i.impact, impact) && same(&i.effort, effort))
+            .collect();
+        cell.sort_by(|a, b| a.sort.cmp(&b.sort));
+        cell
+    };
+
+    if list {
+        for impact in impacts.iter() {
+            for effort in efforts.iter() {
+                let cell = cell(impact, effort);
+                if cell.is_empty() {
+                    continue;
+                }
+                println!(
+                    "{:} {:}, {:} {:}",
+                    "Impact".bold().underline(),
+                    name(impact).bold().underline(),
+                    "effort".bold().underline(),
+                    name(effort).bold().underline()
+                );
+                for item in cell {
+                    println!(
+                        "  {:<12}{:}",
+                        format!("{:}-{:}", space_key, item.number).yellow(),
+                        item.title
+                    );
+                }
+                println!();
+            }
+        }
+        return;
+    }
+
+    let label_width = impacts
+        .iter()
+        .map(|i| name(i).chars().count())
+        .chain(std::iter::once("Impact \\ Effort".len()))
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let width = efforts
+        .iter()
+        .map(|e| name(e).chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4)
+        + 2;
+
+    print!(
+        "{:}",
+        format!("{:<1$}", "Impact \\ Effort", label_width)
+            .bold()
+            .underline()
+    );
+    for effort in efforts.iter() {
+        print!(
+            "{:}",
+            format!("{:>1$}", name(effort), width).bold().underline()
+        );
+    }
+    println!();
+
+    for impact in impacts.iter() {
+        print!("{:}", format!("{:<1$}", name(impact), label_width).bold());
+        for effort in efforts.iter() {
+            let count = cell(impact, effort).len();
+            if count == 0 {
+                print!("{:>1$}", ".", width);
+            } else {
+                print!("{:}", format!("{:>1$}", count, width).yellow());
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,221 @@
+//! A small built-in fuzzy finder used when an argument is left out on a terminal
+
+use anyhow::{bail, Result};
+
+use std::io::{stderr, BufRead, BufReader, IsTerminal, Write};
+
+use termimad::crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+
+use crate::list::truncate;
+
+/// Whether we can ask the user to pick interactively
+pub fn interactive() -> bool {
+    std::io::stdout().is_terminal() && std::io::stdin().is_terminal()
+}
+
+/// Ask for a line of text on the terminal. `None` if the answer was left empty.
+pub fn prompt(question: &str) -> Result<Option<String>> {
+    if !interactive() {
+        bail!("Missing {:}", question.to_lowercase());
+    }
+    let mut out = stderr();
+    execute!(
+        out,
+        SetAttribute(Attribute::Bold),
+        Print(format!("{:}: ", question)),
+        SetAttribute(Attribute::Reset)
+    )?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    Ok(if line.is_empty() {
+        None
+    } else {
+        Some(line.to_string())
+    })
+}
+
+/// Ask a yes/no question, on the terminal even when standard input is piped
+pub fn confirm(question: &str) -> Result<bool> {
+    let mut answer = String::new();
+    if std::io::stdin().is_terminal() {
+        eprint!("{:} [y/N] ", question);
+        std::io::stdin().read_line(&mut answer)?;
+    } else {
+        match std::fs::File::open("/dev/tty") {
+            Ok(tty) => {
+                eprint!("{:} [y/N] ", question);
+                BufReader::new(tty).read_line(&mut answer)?;
+            }
+            Err(_) => bail!("No terminal to ask for confirmation, use --yes"),
+        }
+    }
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Score a candidate by how well the query matches it as a subsequence, rewarding
+/// consecutive characters and matches at the start of words. `None` if it doesn't match.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<usize> {
+    let candidate: Vec<char> = candidate.chars().flat_map(|c| c.to_lowercase()).collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut previous: Option<usize> = None;
+
+    for q in query.chars().flat_map(|c| c.to_lowercase()) {
+        if q.is_whitespace() {
+            continue;
+        }
+        let found = (pos..candidate.len()).find(|&i| candidate[i] == q)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Puts the terminal in raw mode on the alternate screen, and restores it however we exit
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn new() -> Result<TerminalGuard> {
+        terminal::enable_raw_mode()?;
+        execute!(stderr(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn draw(
+    prompt: &str,
+    query: &str,
+    choices: &[String],
+    visible: &[usize],
+    selected: usize,
+    offset: usize,
+) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let (width, rows) = (width as usize, (height as usize).saturating_sub(2));
+    let mut out = stderr();
+
+    queue!(
+        out,
+        terminal::Clear(ClearType::All),
+        cursor::MoveTo(0, 0),
+        SetAttribute(Attribute::Bold),
+        Print(truncate(&format!("{:} > ", prompt), width)),
+        SetAttribute(Attribute::Reset),
+        Print(truncate(query, width)),
+        cursor::MoveTo(0, 1),
+        SetAttribute(Attribute::Dim),
+        Print(format!("  {:}/{:}", visible.len(), choices.len())),
+        SetAttribute(Attribute::Reset),
+    )?;
+
+    for (row, &index) in visible.iter().skip(offset).take(rows).enumerate() {
+        let line = truncate(&format!("  {:}", choices[index]), width);
+        queue!(out, cursor::MoveTo(0, (row + 2) as u16))?;
+        if offset + row == selected {
+            queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(&line),
+                SetAttribute(Attribute::Reset)
+            )?;
+        } else {
+            queue!(out, Print(&line))?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Let the user fuzzy-find one of the choices. Returns its index, or `None` if they
+/// cancelled with Esc or Ctrl-C.
+pub fn pick(prompt: &str, choices: &[String]) -> Result<Option<usize>> {
+    if choices.is_empty() {
+        bail!("Nothing to pick from for {:}", prompt.to_lowercase());
+    }
+
+    let _guard = TerminalGuard::new()?;
+    run(prompt, choices)
+}
+
+/// The picker itself, for callers that already hold a `TerminalGuard`
+pub fn run(prompt: &str, choices: &[String]) -> Result<Option<usize>> {
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut offset = 0;
+
+    loop {
+        let mut scored: Vec<(usize, usize)> = choices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| fuzzy_score(&query, c).map(|s| (s, i)))
+            .collect();
+        // Best score first, otherwise keep the original order
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        let visible: Vec<usize> = scored.into_iter().map(|(_, i)| i).collect();
+
+        selected = selected.min(visible.len().saturating_sub(1));
+        let rows = (terminal::size()?.1 as usize).saturating_sub(2).max(1);
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + rows {
+            offset = selected + 1 - rows;
+        }
+
+        draw(prompt, &query, choices, &visible, selected, offset)?;
+
+        if let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        }) = event::read()?
+        {
+            let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+            match code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if ctrl => return Ok(None),
+                KeyCode::Enter => return Ok(visible.get(selected).copied()),
+                KeyCode::Up | KeyCode::BackTab => selected = selected.saturating_sub(1),
+                KeyCode::Char('p') if ctrl => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Tab => selected += 1,
+                KeyCode::Char('n') if ctrl => selected += 1,
+                KeyCode::PageUp => selected = selected.saturating_sub(rows),
+                KeyCode::PageDown => selected += rows,
+                KeyCode::Backspace => {
+                    query.pop();
+                    selected = 0;
+                }
+                KeyCode::Char('u') if ctrl => {
+                    query.clear();
+                    selected = 0;
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    query.push(c);
+                    selected = 0;
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! A one-line progress counter on standard error, for work that takes a while
+
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Below this many steps the work is quick enough to go without a counter
+const MIN_STEPS: usize = 4;
+
+/// Counts finished steps as `Fetching spaces 3/12`, only when standard error is a terminal
+pub struct Progress {
+    label: &'static str,
+    total: usize,
+    done: AtomicUsize,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Progress {
+        Progress {
+            label,
+            total,
+            done: AtomicUsize::new(0),
+            enabled: total >= MIN_STEPS && std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Count a finished step
+    pub fn tick(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.draw();
+    }
+
+    pub fn draw(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        if self.enabled && done < self.total {
+            eprint!("\r{:} {:}/{:}…", self.label, done, self.total);
+            let _ = std::io::stderr().flush();
+        }
+    }
+
+    /// Erase the counter, e.g. before printing something else
+    pub fn clear(&self) {
+        if self.enabled {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
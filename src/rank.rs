@@ -0,0 +1,161 @@
+//! Sort keys that order work items within a status column, for `km item rank` and
+//! `km item create --position`
+
+use anyhow::{bail, Result};
+
+use std::str::FromStr;
+
+use crate::api::{items_query, Api, WorkItem};
+
+/// Characters used for new sort keys. Existing keys may use others; only their ordering
+/// matters.
+const DIGITS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// A sort key strictly between `before` and `after`, where `None` means the start or end
+/// of the column. Keys compare as plain strings.
+pub fn between(before: Option<&str>, after: Option<&str>) -> Result<String> {
+    let a: Vec<char> = before.unwrap_or("").chars().collect();
+    let b: Option<Vec<char>> = after.map(|b| b.chars().collect());
+    if let Some(b) = &b {
+        if a >= *b {
+            bail!("Sort keys {:?} and {:?} are out of order", before, after);
+        }
+    }
+
+    // Build the key one character at a time while it still equals a prefix of `a`
+    // and/or `b`; as soon as a character fits strictly between them we're done
+    let mut key = String::new();
+    let mut tight_a = true;
+    let mut tight_b = b.is_some();
+    for i in 0.. {
+        let lo = if tight_a { a.get(i).copied() } else { None };
+        let hi = match (&b, tight_b) {
+            (Some(b), true) => match b.get(i) {
+                Some(c) => Some(*c),
+                None => bail!("No sort key fits before {:?}", after),
+            },
+            _ => None,
+        };
+
+        let candidates: Vec<char> = DIGITS
+            .chars()
+            .filter(|c| lo.is_none_or(|l| *c > l) && hi.is_none_or(|h| *c < h))
+            .collect();
+        if !candidates.is_empty() {
+            key.push(candidates[candidates.len() / 2]);
+            return Ok(key);
+        }
+
+        let c = match (lo, hi) {
+            (Some(l), _) => l,
+            (None, Some(h)) => match (h as u32).checked_sub(1).and_then(char::from_u32) {
+                Some(c) if c != '\0' => c,
+                _ => bail!("No sort key fits before {:?}", after),
+            },
+            (None, None) => unreachable!("every digit fits without bounds"),
+        };
+        tight_a = tight_a && a.get(i) == Some(&c);
+        tight_b = tight_b && hi == Some(c);
+        key.push(c);
+    }
+    unreachable!()
+}
+
+/// The work items in a status column, top first
+pub async fn column(
+    api: &Api,
+    space_id: &str,
+    status_id: &str,
+    status_type: items_query::StatusType,
+) -> Result<Vec<WorkItem>> {
+    let status_filter = items_query::StatusFilter {
+        type_: items_query::StatusFilterType::INCLUDE,
+        filter: vec![status_type],
+    };
+    let mut items: Vec<WorkItem> = api
+        .work_items(space_id, Some(status_filter), None)
+        .await?
+        .into_iter()
+        .filter(|i| i.status.id == status_id)
+        .collect();
+    items.sort_by(|a, b| a.sort.cmp(&b.sort));
+    Ok(items)
+}
+
+/// Where a new work item goes in its column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Position {
+    Top,
+    Bottom,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "top" => Ok(Position::Top),
+            "bottom" => Ok(Position::Bottom),
+            _ => Err(format!("Unknown position {:} (top or bottom)", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_between(before: Option<&str>, after: Option<&str>) -> String {
+        let key = between(before, after).unwrap();
+        assert!(
+            before.is_none_or(|b| b < key.as_str()),
+            "{:?} !< {:?}",
+            before,
+            key
+        );
+        assert!(
+            after.is_none_or(|a| key.as_str() < a),
+            "{:?} !< {:?}",
+            key,
+            after
+        );
+        key
+    }
+
+    #[test]
+    fn empty_column() {
+        assert_eq!(assert_between(None, None).len(), 1);
+    }
+
+    #[test]
+    fn prefix() {
+        assert_between(Some("a"), Some("ab"));
+        assert_between(Some("a"), Some("a0"));
+        assert_between(Some("a"), Some("a00"));
+    }
+
+    #[test]
+    fn below_first_digit() {
+        assert_between(None, Some("!"));
+        assert_between(None, Some("0"));
+        assert_between(Some("!"), Some("#"));
+        assert_between(Some(" "), Some("!"));
+    }
+
+    #[test]
+    fn after_last_digit() {
+        assert_eq!(assert_between(Some("z"), None).chars().next(), Some('z'));
+        assert_between(Some("zzz"), None);
+        assert_between(Some("~"), None);
+        assert_between(Some("z"), Some("~"));
+    }
+
+    #[test]
+    fn no_room() {
+        assert!(between(Some("a"), Some("a")).is_err());
+        assert!(between(Some("b"), Some("a")).is_err());
+        assert!(between(Some("a"), Some("a\u{0}")).is_err());
+        assert!(between(None, Some("\u{0}")).is_err());
+        assert!(between(None, Some("")).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+//! Hand-written queries and mutations for `km api`
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use colored::*;
+
+use serde_json::{json, Map, Value};
+
+use crate::api::Api;
+use crate::schema::Schema;
+
+/// The query text from the argument, a file (`@FILE`) or standard input (`-`)
+fn query_text(arg: &str) -> Result<String> {
+    if arg == "-" {
+        return Ok(std::io::read_to_string(std::io::stdin())?);
+    }
+    match arg.strip_prefix('@') {
+        Some(path) => {
+            std::fs::read_to_string(path).with_context(|| format!("Could not read {:}", path))
+        }
+        None => Ok(arg.to_string()),
+    }
+}
+
+/// Split `NAME=VALUE` variables into a JSON object, parsing values as JSON if `typed`
+fn add_variables(variables: &mut Map<String, Value>, fields: &[String], typed: bool) -> Result<()> {
+    for field in fields {
+        let (name, value) = field
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid variable {:} (expected NAME=VALUE)", field))?;
+        let value = match serde_json::from_str(value) {
+            Ok(parsed) if typed => parsed,
+            _ => Value::String(value.to_string()),
+        };
+        variables.insert(name.to_string(), value);
+    }
+    Ok(())
+}
+
+pub async fn run(
+    api: &Api,
+    query: &str,
+    fields: &[String],
+    raw_fields: &[String],
+    operation: Option<String>,
+    validate: bool,
+) -> Result<()> {
+    let query = query_text(query)?;
+
+    if validate {
+        let problems = Schema::bundled().validate(&query)?;
+        if !problems.is_empty() {
+            for problem in problems.iter() {
+                eprintln!("{:} {:}", "error:".red().bold(), problem);
+            }
+            bail!(
+                "The query doesn't match the bundled schema ({:} {:})",
+                problems.len(),
+                if problems.len() == 1 {
+                    "problem"
+                } else {
+                    "problems"
+                }
+            );
+        }
+    }
+
+    let mut variables = Map::new();
+    add_variables(&mut variables, fields, true)?;
+    add_variables(&mut variables, raw_fields, false)?;
+
+    let mut body = json!({ "query": query, "variables": variables });
+    if let Some(operation) = operation {
+        body["operationName"] = Value::String(operation);
+    }
+
+    let response = api.raw(&body).await?;
+    println!("{:}", serde_json::to_string_pretty(&response)?);
+
+    if response.get("errors").is_some_and(|e| !e.is_null()) {
+        bail!("The query returned errors");
+    }
+    Ok(())
+}
@@ -0,0 +1,269 @@
+//! Turning command line arguments into spaces, statuses, users and labels, falling back
+//! to the fuzzy picker when an argument is left out on a terminal
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::api::{
+    item_by_number_query, items_query, space_query, Api, ItemByNumberQuery, SpaceQuery,
+};
+use crate::cache;
+use crate::config::Config;
+use crate::picker;
+
+pub type Organization = space_query::SpaceQueryOrganization;
+pub type Space = space_query::SpaceQueryOrganizationSpaces;
+pub type Status = space_query::SpaceQueryOrganizationSpacesStatuses;
+pub type Label = space_query::SpaceQueryOrganizationSpacesLabels;
+pub type User = space_query::SpaceQueryOrganizationUsers;
+pub type WorkItem = item_by_number_query::ItemByNumberQueryWorkItemByNumber;
+
+/// Fetch the organization's spaces, statuses, labels and users, keeping a copy in the
+/// cache for shell completion
+pub async fn organization(api: &Api) -> Result<Organization> {
+    let org = api
+        .query::<SpaceQuery>(space_query::Variables {})
+        .await?
+        .organization;
+    // Completion works without it, just less well
+    let _ = cache::store(cache::ORGANIZATION, &org);
+    Ok(org)
+}
+
+/// Split `ABC-123` into the space key and the number
+pub fn split_number(number: &str) -> Result<(&str, &str)> {
+    match number.rsplit_once('-') {
+        Some((space, n)) if !space.is_empty() && !n.is_empty() => Ok((space, n)),
+        _ => bail!(
+            "Invalid work item number {:} (expected e.g. ABC-123)",
+            number
+        ),
+    }
+}
+
+pub fn space<'a>(org: &'a Organization, key: &str) -> Result<&'a Space> {
+    org.spaces
+        .iter()
+        .find(|s| s.key.eq_ignore_ascii_case(key))
+        .ok_or_else(|| anyhow!("Could not find space {:}", key))
+}
+
+/// The space with the given key, or picked interactively if left out
+pub fn space_or_pick<'a>(org: &'a Organization, key: Option<&str>) -> Result<&'a Space> {
+    choose(
+        "space",
+        &org.spaces,
+        key,
+        |s| format!("{:<8}{:}", s.key, s.name),
+        |s, key| s.key.eq_ignore_ascii_case(key),
+    )
+}
+
+/// Use the argument if given, otherwise let the user pick one of the options
+fn choose<'a, T>(
+    what: &str,
+    options: &'a [T],
+    arg: Option<&str>,
+    display: impl Fn(&T) -> String,
+    matches: impl Fn(&T, &str) -> bool,
+) -> Result<&'a T> {
+    match arg {
+        Some(arg) => options.iter().find(|o| matches(o, arg)).ok_or_else(|| {
+            let names: Vec<String> = options.iter().map(&display).collect();
+            anyhow!("Unknown {:} {:} (one of: {:})", what, arg, names.join(", "))
+        }),
+        None => {
+            if !picker::interactive() {
+                bail!("Missing {:}", what);
+            }
+            let names: Vec<String> = options.iter().map(&display).collect();
+            match picker::pick(&format!("Pick {:}", what), &names)? {
+                Some(i) => Ok(&options[i]),
+                None => bail!("Cancelled"),
+            }
+        }
+    }
+}
+
+/// Work item numbers from standard input, one per line. Only the first field of each line
+/// is used, so tab-separated output can be piped in as is.
+pub fn read_numbers() -> Result<Vec<String>> {
+    let text = std::io::read_to_string(std::io::stdin())?;
+    Ok(text
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|number| number.to_string())
+        .collect())
+}
+
+/// Whether the argument is a work item number in one of the spaces, e.g. ABC-123
+fn is_number(org: &Organization, arg: &str) -> bool {
+    split_number(arg).is_ok_and(|(key, n)| {
+        n.chars().all(|c| c.is_ascii_digit())
+            && org.spaces.iter().any(|s| s.key.eq_ignore_ascii_case(key))
+    })
+}
+
+/// Work item numbers from the arguments, where `-` reads them from standard input. With
+/// no arguments, one is picked interactively.
+pub async fn item_numbers(api: &Api, org: &Organization, args: Vec<String>) -> Result<Vec<String>> {
+    if args.is_empty() {
+        return Ok(vec![item_number(api, org, None).await?]);
+    }
+    let mut numbers = Vec::new();
+    for arg in args {
+        if arg == "-" {
+            numbers.extend(read_numbers()?);
+        } else {
+            numbers.push(arg);
+        }
+    }
+    Ok(numbers)
+}
+
+/// Work item numbers at the start of the arguments, as for `item_numbers`, and the one
+/// argument that may follow them
+pub async fn item_numbers_and(
+    api: &Api,
+    org: &Organization,
+    mut args: Vec<String>,
+    what: &str,
+) -> Result<(Vec<String>, Option<String>)> {
+    let split = args
+        .iter()
+        .position(|a| a != "-" && !is_number(org, a))
+        .unwrap_or(args.len());
+    let rest = args.split_off(split);
+    if rest.len() > 1 {
+        bail!(
+            "Expected a single {:} after the work item numbers, got {:}",
+            what,
+            rest.join(" ")
+        );
+    }
+    let numbers = item_numbers(api, org, args).await?;
+    Ok((numbers, rest.into_iter().next()))
+}
+
+/// The work item number from the argument, or picked from all open items
+pub async fn item_number(api: &Api, org: &Organization, arg: Option<String>) -> Result<String> {
+    if let Some(number) = arg {
+        return Ok(number);
+    }
+    if !picker::interactive() {
+        bail!("Missing work item number");
+    }
+
+    let status_filter = items_query::StatusFilter {
+        type_: items_query::StatusFilterType::EXCLUDE,
+        filter: vec![items_query::StatusType::ARCHIVED],
+    };
+    let mut choices = Vec::new();
+    for spc in org.spaces.iter() {
+        for item in api
+            .work_items(&spc.id, Some(status_filter.clone()), None)
+            .await?
+        {
+            choices.push((
+                format!("{:}-{:}", spc.key, item.number),
+                item.status.name,
+                item.title,
+            ));
+        }
+    }
+
+    let item = choose(
+        "work item",
+        &choices,
+        None,
+        |(number, status, title)| format!("{:<12}{:<20}{:}", number, status, title),
+        |_, _| false,
+    )?;
+    Ok(item.0.clone())
+}
+
+/// Look up a work item by its number
+pub async fn item(api: &Api, number: &str) -> Result<WorkItem> {
+    split_number(number)?;
+    let response_data = api
+        .query::<ItemByNumberQuery>(item_by_number_query::Variables {
+            number: number.to_string(),
+        })
+        .await?;
+    Ok(response_data.work_item_by_number)
+}
+
+pub fn status<'a>(space: &'a Space, arg: Option<&str>) -> Result<&'a Status> {
+    choose(
+        "status",
+        &space.statuses,
+        arg,
+        |s| s.name.clone(),
+        |s, name| s.name.eq_ignore_ascii_case(name),
+    )
+}
+
+/// Where new work items go unless told otherwise
+pub fn default_status(space: &Space) -> Result<&Status> {
+    space
+        .statuses
+        .iter()
+        .find(|&st| {
+            st.default
+                && (st.type_ == space_query::StatusType::BACKLOG
+                    || st.type_ == space_query::StatusType::TODO)
+        })
+        .ok_or_else(|| {
+            anyhow!(
+                "Space {:} has no default backlog or todo status, pick one with --status",
+                space.key
+            )
+        })
+}
+
+/// Where work items go when work on them starts: the default in-progress status, or the
+/// first one
+pub fn in_progress_status(space: &Space) -> Result<&Status> {
+    let in_progress = |st: &&Status| st.type_ == space_query::StatusType::IN_PROGRESS;
+    space
+        .statuses
+        .iter()
+        .filter(in_progress)
+        .find(|st| st.default)
+        .or_else(|| space.statuses.iter().find(in_progress))
+        .ok_or_else(|| anyhow!("Space {:} has no in-progress status", space.key))
+}
+
+pub fn label<'a>(space: &'a Space, arg: Option<&str>) -> Result<&'a Label> {
+    choose(
+        "label",
+        &space.labels,
+        arg,
+        |l| l.name.clone(),
+        |l, name| l.name.eq_ignore_ascii_case(name),
+    )
+}
+
+/// Active users, matched by username; `me` is the username from the config
+pub fn user<'a>(org: &'a Organization, config: &Config, arg: Option<&str>) -> Result<&'a User> {
+    let arg = match arg {
+        Some(a) if a.eq_ignore_ascii_case("me") => Some(
+            config
+                .username
+                .as_deref()
+                .ok_or_else(|| anyhow!("Set username in {:}", Config::path().display()))?,
+        ),
+        other => other,
+    };
+    let users: Vec<&User> = org.users.iter().filter(|u| !u.deactivated).collect();
+    let user = choose(
+        "user",
+        &users,
+        arg,
+        |u| match &u.name {
+            Some(name) => format!("{:} ({:})", u.username, name),
+            None => u.username.clone(),
+        },
+        |u, name| u.username.eq_ignore_ascii_case(name),
+    )?;
+    Ok(user)
+}
@@ -0,0 +1,67 @@
+//! When and how long to wait before sending a failed request again
+//!
+//! Queries are retried on timeouts, connection errors, 429 and 5xx gateway errors, with
+//! exponential backoff and jitter, or as long as a 429's `Retry-After` asks (up to a
+//! limit). Mutations aren't idempotent, so they are only retried when asked to.
+
+use chrono::{DateTime, Utc};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+/// Delay before the first retry, doubled for every retry after it
+const BASE: Duration = Duration::from_millis(500);
+
+/// Longest backoff between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(20);
+
+/// A `Retry-After` longer than this isn't worth waiting for, the error is returned instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Statuses that are likely to go away if the request is sent again
+pub fn is_transient(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// How long the server asked us to wait, in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Exponential backoff with "equal jitter": half the delay is fixed, the other half random
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF);
+    let random = RandomState::new().hash_one(attempt) % 1000;
+    delay / 2 + delay / 2 * random as u32 / 1000
+}
+
+/// How long to wait before retry number `attempt` (counting from 0), or `None` if the
+/// server wants us to wait longer than we are willing to
+pub fn delay(attempt: u32, headers: Option<&HeaderMap>) -> Option<Duration> {
+    match headers.and_then(retry_after) {
+        Some(wait) if wait > MAX_RETRY_AFTER => None,
+        Some(wait) => Some(wait),
+        None => Some(backoff(attempt)),
+    }
+}
@@ -0,0 +1,178 @@
+//! Code review requests (pull and merge requests) linked to work items, for `km review`
+//! and the code reviews in `km item view`
+
+use anyhow::{bail, Result};
+
+use colored::*;
+
+use serde_json::Value;
+
+use crate::api::{
+    code_review_query, edit_code_review_request, item_query, Api, CodeReviewQuery,
+    EditCodeReviewRequest,
+};
+
+pub type CodeReview = code_review_query::CodeReviewQueryCodeReviewRequest;
+type State = code_review_query::CodeReviewRequestState;
+
+fn state(state: &State) -> ColoredString {
+    match state {
+        State::OPEN => "OPEN".green(),
+        State::MERGED => "MERGED".magenta(),
+        State::CLOSED => "CLOSED".red(),
+        State::LOCKED => "LOCKED".dimmed(),
+        State::Other(other) => other.normal(),
+    }
+}
+
+/// Look up a code review request by its URL
+pub async fn find(api: &Api, url: &str) -> Result<CodeReview> {
+    let response_data = api
+        .query::<CodeReviewQuery>(code_review_query::Variables {
+            url: url.to_string(),
+        })
+        .await?;
+    Ok(response_data.code_review_request)
+}
+
+/// Print a code review request with its links and work items
+pub fn print(review: &CodeReview) {
+    println!(
+        "{:} {:} {:}",
+        format!("#{:}", review.details.number).bold(),
+        review.details.title.bold(),
+        state(&review.state)
+    );
+    println!("{:}", review.details.url.dimmed());
+
+    if !review.links.is_empty() {
+        println!("\n{:}", "Links:".bold());
+        let width = review.links.iter().map(|l| l.name.len()).max().unwrap_or(0);
+        for link in review.links.iter() {
+            println!("  {:<width$}  {:}", link.name, link.url, width = width);
+        }
+    }
+
+    println!("\n{:}", "Work items:".bold());
+    if review.work_items.is_empty() {
+        println!("  {:}", "none".dimmed());
+    }
+    for item in review.work_items.iter() {
+        let number = format!("{:}-{:}", item.space.key, item.number);
+        println!(
+            "  {:<10}{:<20}{:}",
+            number.yellow().bold(),
+            item.status.name,
+            item.title
+        );
+    }
+}
+
+/// Split `name=url` as given to `--add`
+pub fn parse_link(arg: &str) -> Result<(String, String)> {
+    match arg.split_once('=') {
+        Some((name, url)) if !name.trim().is_empty() && !url.trim().is_empty() => {
+            Ok((name.trim().to_string(), url.trim().to_string()))
+        }
+        _ => bail!("Invalid link {:} (expected NAME=URL)", arg),
+    }
+}
+
+/// Add links to a code review request, replacing those with the same name, and remove
+/// the named ones. The API sets all links at once, so the others are sent back as they are.
+pub async fn edit_links(
+    api: &Api,
+    review: &CodeReview,
+    add: &[(String, String)],
+    remove: &[String],
+) -> Result<()> {
+    for name in remove.iter() {
+        if !review.links.iter().any(|l| &l.name == name) {
+            bail!("{:} has no link named {:}", review.details.url, name);
+        }
+    }
+    let mut links: Vec<edit_code_review_request::CodeReviewRequestLinkInput> = review
+        .links
+        .iter()
+        .filter(|l| !remove.contains(&l.name) && !add.iter().any(|(name, _)| name == &l.name))
+        .map(|l| edit_code_review_request::CodeReviewRequestLinkInput {
+            name: l.name.clone(),
+            url: l.url.clone(),
+        })
+        .collect();
+    links.extend(add.iter().map(|(name, url)| {
+        edit_code_review_request::CodeReviewRequestLinkInput {
+            name: name.clone(),
+            url: url.clone(),
+        }
+    }));
+
+    api.query::<EditCodeReviewRequest>(edit_code_review_request::Variables {
+        id: review.id.clone(),
+        links: Some(links),
+    })
+    .await?;
+    Ok(())
+}
+
+/// The first URL in an activity's details. Their shape isn't part of the stable API, so
+/// don't rely on any particular field.
+fn url_in(details: &Value) -> Option<String> {
+    match details {
+        Value::String(s) if s.starts_with("https://") || s.starts_with("http://") => {
+            Some(s.clone())
+        }
+        Value::Array(values) => values.iter().find_map(url_in),
+        Value::Object(map) => map
+            .get("url")
+            .and_then(url_in)
+            .or_else(|| map.values().find_map(url_in)),
+        _ => None,
+    }
+}
+
+/// The code review requests added to a work item, by URL, with what could be looked up
+/// about them
+pub async fn for_item(
+    api: &Api,
+    activities: &[item_query::ItemQueryWorkItemActivities],
+) -> Vec<(String, Option<CodeReview>)> {
+    let mut urls: Vec<String> = Vec::new();
+    for activity in activities.iter() {
+        if activity.type_ != item_query::ActivityType::CODE_REVIEW_REQUEST_ADDED {
+            continue;
+        }
+        if let Some(url) = url_in(&activity.details) {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    }
+    let mut reviews = Vec::new();
+    for url in urls {
+        let review = find(api, &url).await.ok();
+        reviews.push((url, review));
+    }
+    reviews
+}
+
+/// Print code review requests with their state, as part of `km item view`. Ones that
+/// couldn't be looked up are shown by URL only.
+pub fn print_for_item(reviews: &[(String, Option<CodeReview>)]) {
+    if reviews.is_empty() {
+        return;
+    }
+    println!("Code reviews:");
+    for (url, review) in reviews.iter() {
+        match review {
+            Some(review) => println!(
+                "  {:} {:} {:} {:}",
+                state(&review.state),
+                format!("#{:}", review.details.number).bold(),
+                review.details.title,
+                review.details.url.dimmed()
+            ),
+            None => println!("  {:}", url.dimmed()),
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! The bundled GraphQL schema, checking queries against it for `km api --validate` and
+//! comparing it with the live one for `km schema check`
+
+use anyhow::{anyhow, Result};
+
+use colored::*;
+
+use graphql_parser::query::{self, Definition, OperationDefinition, Selection, SelectionSet};
+use graphql_parser::schema::{self, TypeDefinition};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// The schema the queries in `queries.graphql` are generated from
+pub const BUNDLED: &str = include_str!("kitemaker.graphql");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Scalar,
+    Object,
+    Interface,
+    Union,
+    Enum,
+    InputObject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    /// The type as written in SDL, e.g. `[WorkItem!]!`
+    pub type_: String,
+    pub args: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Type {
+    pub kind: Kind,
+    /// Fields of objects and interfaces, or input fields of input objects
+    pub fields: BTreeMap<String, Field>,
+    /// Values of enums, or member types of unions
+    pub values: Vec<String>,
+}
+
+/// The parts of a schema we validate against
+#[derive(Debug)]
+pub struct Schema {
+    pub types: BTreeMap<String, Type>,
+    pub query: String,
+    pub mutation: Option<String>,
+}
+
+fn input_fields(values: &[schema::InputValue<'_, String>]) -> BTreeMap<String, Field> {
+    values
+        .iter()
+        .map(|v| {
+            (
+                v.name.clone(),
+                Field {
+                    type_: v.value_type.to_string(),
+                    args: BTreeMap::new(),
+                },
+            )
+        })
+        .collect()
+}
+
+fn fields(fields: &[schema::Field<'_, String>]) -> BTreeMap<String, Field> {
+    fields
+        .iter()
+        .map(|f| {
+            (
+                f.name.clone(),
+                Field {
+                    type_: f.field_type.to_string(),
+                    args: input_fields(&f.arguments)
+                        .into_iter()
+                        .map(|(name, field)| (name, field.type_))
+                        .collect(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// The named type inside list and non-null wrappers, e.g. `WorkItem` for `[WorkItem!]!`
+pub fn named(type_: &str) -> &str {
+    type_.trim_matches(['[', ']', '!'])
+}
+
+impl Schema {
+    pub fn parse(sdl: &str) -> Result<Schema> {
+        let document = schema::parse_schema::<String>(sdl)?;
+        let mut schema = Schema {
+            types: BTreeMap::new(),
+            query: "Query".to_string(),
+            mutation: Some("Mutation".to_string()),
+        };
+
+        for definition in document.definitions {
+            match definition {
+                schema::Definition::SchemaDefinition(d) => {
+                    if let Some(query) = d.query {
+                        schema.query = query;
+                    }
+                    schema.mutation = d.mutation;
+                }
+                schema::Definition::TypeDefinition(t) => {
+                    let (name, type_) = match t {
+                        TypeDefinition::Scalar(t) => (t.name, Type::new(Kind::Scalar)),
+                        TypeDefinition::Object(t) => (
+                            t.name,
+                            Type {
+                                fields: fields(&t.fields),
+                                ..Type::new(Kind::Object)
+                            },
+                        ),
+                        TypeDefinition::Interface(t) => (
+                            t.name,
+                            Type {
+                                fields: fields(&t.fields),
+                                ..Type::new(Kind::Interface)
+                            },
+                        ),
+                        TypeDefinition::Union(t) => (
+                            t.name,
+                            Type {
+                                values: t.types,
+                                ..Type::new(Kind::Union)
+                            },
+                        ),
+                        TypeDefinition::Enum(t) => (
+                            t.name,
+                            Type {
+                                values: t.values.into_iter().map(|v| v.name).collect(),
+                                ..Type::new(Kind::Enum)
+                            },
+                        ),
+                        TypeDefinition::InputObject(t) => (
+                            t.name,
+                            Type {
+                                fields: input_fields(&t.fields),
+                                ..Type::new(Kind::InputObject)
+                            },
+                        ),
+                    };
+                    schema.types.insert(name, type_);
+                }
+                schema::Definition::TypeExtension(_)
+                | schema::Definition::DirectiveDefinition(_) => {}
+            }
+        }
+
+        for scalar in ["String", "Int", "Float", "Boolean", "ID"] {
+            schema
+                .types
+                .entry(scalar.to_string())
+                .or_insert_with(|| Type::new(Kind::Scalar));
+        }
+        if let Some(mutation) = &schema.mutation {
+            if !schema.types.contains_key(mutation) {
+                schema.mutation = None;
+            }
+        }
+        Ok(schema)
+    }
+
+    pub fn bundled() -> Schema {
+        Schema::parse(BUNDLED).expect("bundled schema is valid")
+    }
+
+    /// Problems with the query, such as unknown fields or arguments and missing
+    /// selections. Fails only if the query can't be parsed at all.
+    pub fn validate(&self, text: &str) -> Result<Vec<String>> {
+        let document =
+            query::parse_query::<String>(text).map_err(|e| anyhow!("Invalid query: {:}", e))?;
+
+        let mut fragments = HashMap::new();
+        for definition in document.definitions.iter() {
+            if let Definition::Fragment(f) = definition {
+                let query::TypeCondition::On(on) = &f.type_condition;
+                fragments.insert(f.name.as_str(), (on.as_str(), &f.selection_set));
+            }
+        }
+
+        let mut validator = Validator {
+            schema: self,
+            fragments,
+            visited: HashSet::new(),
+            errors: Vec::new(),
+        };
+        for definition in document.definitions.iter() {
+            let (root, variables, selection_set) = match definition {
+                Definition::Fragment(_) => continue,
+                Definition::Operation(OperationDefinition::SelectionSet(s)) => {
+                    (Some(self.query.as_str()), &[][..], s)
+                }
+                Definition::Operation(OperationDefinition::Query(q)) => (
+                    Some(self.query.as_str()),
+                    &q.variable_definitions[..],
+                    &q.selection_set,
+                ),
+                Definition::Operation(OperationDefinition::Mutation(m)) => (
+                    self.mutation.as_deref(),
+                    &m.variable_definitions[..],
+                    &m.selection_set,
+                ),
+                Definition::Operation(OperationDefinition::Subscription(s)) => {
+                    validator
+                        .errors
+                        .push("Subscriptions are not supported".to_string());
+                    (None, &s.variable_definitions[..], &s.selection_set)
+                }
+            };
+
+            for variable in variables {
+                let type_ = variable.var_type.to_string();
+                match self.types.get(named(&type_)).map(|t| t.kind) {
+                    Some(Kind::Scalar | Kind::Enum | Kind::InputObject) => {}
+                    Some(_) => validator.errors.push(format!(
+                        "Variable ${:} must be of an input type, not {:}",
+                        variable.name, type_
+                    )),
+                    None => validator.errors.push(format!(
+                        "Unknown type {:} of variable ${:}",
+                        named(&type_),
+                        variable.name
+                    )),
+                }
+            }
+
+            match root {
+                Some(root) => validator.selection_set(root, selection_set),
+                None => validator
+                    .errors
+                    .push("The schema has no mutations".to_string()),
+            }
+        }
+        Ok(validator.errors)
+    }
+}
+
+impl Kind {
+    fn keyword(&self) -> &'static str {
+        match self {
+            Kind::Scalar => "scalar",
+            Kind::Object => "type",
+            Kind::Interface => "interface",
+            Kind::Union => "union",
+            Kind::Enum => "enum",
+            Kind::InputObject => "input",
+        }
+    }
+}
+
+impl Type {
+    fn new(kind: Kind) -> Type {
+        Type {
+            kind,
+            fields: BTreeMap::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+struct Validator<'a> {
+    schema: &'a Schema,
+    fragments: HashMap<&'a str, (&'a str, &'a SelectionSet<'a, String>)>,
+    /// Fragments already checked, so fragments spreading each other don't loop
+    visited: HashSet<&'a str>,
+    errors: Vec<String>,
+}
+
+impl<'a> Validator<'a> {
+    fn selection_set(&mut self, type_name: &str, selection_set: &'a SelectionSet<'a, String>) {
+        let type_ = match self.schema.types.get(type_name) {
+            Some(t) => t,
+            None => {
+                self.errors.push(format!("Unknown type {:}", type_name));
+                return;
+            }
+        };
+
+        for selection in selection_set.items.iter() {
+            match selection {
+                Selection::Field(field) => self.field(type_name, type_, field),
+                Selection::InlineFragment(fragment) => {
+                    let on = match &fragment.type_condition {
+                        Some(query::TypeCondition::On(on)) => on.as_str(),
+                        None => type_name,
+                    };
+                    if type_.kind == Kind::Union
+                        && on != type_name
+                        && !type_.values.iter().any(|v| v == on)
+                    {
+                        self.errors.push(format!(
+                            "Fragment on {:} can never match {:}, which is one of {:}",
+                            on,
+                            type_name,
+                            type_.values.join(", ")
+                        ));
+                        continue;
+                    }
+                    self.selection_set(on, &fragment.selection_set);
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = spread.fragment_name.as_str();
+                    match self.fragments.get(name).copied() {
+                        None => self.errors.push(format!("Unknown fragment {:}", name)),
+                        Some((on, selection_set)) => {
+                            if self.visited.insert(name) {
+                                self.selection_set(on, selection_set);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn field(&mut self, type_name: &str, type_: &Type, field: &'a query::Field<'a, String>) {
+        let name = field.name.as_str();
+        if name == "__typename" {
+            return;
+        }
+        // Introspection isn't part of the SDL, so take it on trust
+        if name.starts_with("__") && type_name == self.schema.query {
+            return;
+        }
+
+        let definition = match type_.fields.get(name) {
+            Some(d) if type_.kind != Kind::InputObject => d,
+            _ => {
+                self.errors.push(format!(
+                    "Cannot query field {:} on type {:}",
+                    name, type_name
+                ));
+                return;
+            }
+        };
+
+        for (arg, _) in field.arguments.iter() {
+            if !definition.args.contains_key(arg) {
+                self.errors.push(format!(
+                    "Unknown argument {:} on field {:}.{:}",
+                    arg, type_name, name
+                ));
+            }
+        }
+        for (arg, arg_type) in definition.args.iter() {
+            if arg_type.ends_with('!') && !field.arguments.iter().any(|(a, _)| a == arg) {
+                self.errors.push(format!(
+                    "Missing required argument {:} on field {:}.{:}",
+                    arg, type_name, name
+                ));
+            }
+        }
+
+        let field_type = named(&definition.type_);
+        let composite = matches!(
+            self.schema.types.get(field_type).map(|t| t.kind),
+            Some(Kind::Object | Kind::Interface | Kind::Union)
+        );
+        let selected = !field.selection_set.items.is_empty();
+        if composite && !selected {
+            self.errors.push(format!(
+                "Field {:}.{:} of type {:} needs a selection of subfields",
+                type_name, name, definition.type_
+            ));
+        } else if !composite && selected {
+            self.errors.push(format!(
+                "Field {:}.{:} of type {:} can't have a selection of subfields",
+                type_name, name, definition.type_
+            ));
+        } else if composite {
+            self.selection_set(field_type, &field.selection_set);
+        }
+    }
+}
+
+/// A difference between two schemas
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+impl Change {
+    /// Removals and changes can break queries written against the old schema
+    pub fn breaking(&self) -> bool {
+        !matches!(self, Change::Added(_))
+    }
+}
+
+/// Added, removed and changed entries of two maps, described by `describe`
+fn diff_maps<T: PartialEq>(
+    old: &BTreeMap<String, T>,
+    new: &BTreeMap<String, T>,
+    changes: &mut Vec<Change>,
+    describe: impl Fn(&str, &T) -> String,
+    changed: impl Fn(&str, &T, &T) -> Vec<Change>,
+) {
+    for (name, value) in old.iter() {
+        match new.get(name) {
+            None => changes.push(Change::Removed(describe(name, value))),
+            Some(new_value) if new_value != value => {
+                changes.extend(changed(name, value, new_value))
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, value) in new.iter() {
+        if !old.contains_key(name) {
+            changes.push(Change::Added(describe(name, value)));
+        }
+    }
+}
+
+fn diff_fields(type_name: &str, old: &Type, new: &Type, changes: &mut Vec<Change>) {
+    diff_maps(
+        &old.fields,
+        &new.fields,
+        changes,
+        |name, field| format!("{:}.{:}: {:}", type_name, name, field.type_),
+        |name, old, new| {
+            let path = format!("{:}.{:}", type_name, name);
+            let mut changes = Vec::new();
+            if old.type_ != new.type_ {
+                changes.push(Change::Changed(format!(
+                    "{:}: {:} → {:}",
+                    path, old.type_, new.type_
+                )));
+            }
+            diff_maps(
+                &old.args,
+                &new.args,
+                &mut changes,
+                |arg, type_| format!("{:}({:}: {:})", path, arg, type_),
+                |arg, old, new| {
+                    vec![Change::Changed(format!(
+                        "{:}({:}): {:} → {:}",
+                        path, arg, old, new
+                    ))]
+                },
+            );
+            changes
+        },
+    );
+}
+
+fn diff_values(type_name: &str, old: &Type, new: &Type, changes: &mut Vec<Change>) {
+    let separator = if old.kind == Kind::Union { " | " } else { "." };
+    for value in old.values.iter() {
+        if !new.values.contains(value) {
+            changes.push(Change::Removed(format!(
+                "{:}{:}{:}",
+                type_name, separator, value
+            )));
+        }
+    }
+    for value in new.values.iter() {
+        if !old.values.contains(value) {
+            changes.push(Change::Added(format!(
+                "{:}{:}{:}",
+                type_name, separator, value
+            )));
+        }
+    }
+}
+
+/// Types, fields, arguments, enum values and union members that differ, sorted with
+/// additions first
+pub fn diff(old: &Schema, new: &Schema) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (name, old_type) in old.types.iter() {
+        match new.types.get(name) {
+            None => changes.push(Change::Removed(format!(
+                "{:} {:}",
+                old_type.kind.keyword(),
+                name
+            ))),
+            Some(new_type) if new_type.kind != old_type.kind => {
+                changes.push(Change::Changed(format!(
+                    "{:}: {:} → {:}",
+                    name,
+                    old_type.kind.keyword(),
+                    new_type.kind.keyword()
+                )))
+            }
+            Some(new_type) => {
+                diff_fields(name, old_type, new_type, &mut changes);
+                diff_values(name, old_type, new_type, &mut changes);
+            }
+        }
+    }
+    for (name, new_type) in new.types.iter() {
+        if !old.types.contains_key(name) {
+            changes.push(Change::Added(format!(
+                "{:} {:}",
+                new_type.kind.keyword(),
+                name
+            )));
+        }
+    }
+    changes.sort();
+    changes
+}
+
+pub fn print_changes(changes: &[Change]) {
+    for change in changes {
+        match change {
+            Change::Added(what) => println!("{:} {:}", "+".green().bold(), what),
+            Change::Removed(what) => println!("{:} {:}", "-".red().bold(), what),
+            Change::Changed(what) => println!("{:} {:}", "~".yellow().bold(), what),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "
+        type Query { item(id: ID!): Item, items(first: Int): [Item!]! }
+        type Item { id: ID!, title: String, state: State }
+        enum State { OPEN CLOSED }
+        union Result = Item | Error
+        type Error { message: String! }
+        input Filter { title: String }
+    ";
+
+    const NEW: &str = "
+        type Query { item(id: ID!, archived: Boolean): Item, items(first: String): [Item!]! }
+        type Item { id: ID!, title: String!, state: State, sort: String }
+        enum State { OPEN MERGED }
+        union Result = Item
+        type Error { message: String! }
+        scalar Filter
+    ";
+
+    fn diff_sdl(old: &str, new: &str) -> Vec<Change> {
+        diff(&Schema::parse(old).unwrap(), &Schema::parse(new).unwrap())
+    }
+
+    #[test]
+    fn unchanged() {
+        assert!(diff_sdl(OLD, OLD).is_empty());
+        assert!(diff(&Schema::bundled(), &Schema::bundled()).is_empty());
+    }
+
+    #[test]
+    fn changes() {
+        let added = |s: &str| Change::Added(s.to_string());
+        let removed = |s: &str| Change::Removed(s.to_string());
+        let changed = |s: &str| Change::Changed(s.to_string());
+        assert_eq!(
+            diff_sdl(OLD, NEW),
+            vec![
+                added("Item.sort: String"),
+                added("Query.item(archived: Boolean)"),
+                added("State.MERGED"),
+                removed("Result | Error"),
+                removed("State.CLOSED"),
+                changed("Filter: input → scalar"),
+                changed("Item.title: String → String!"),
+                changed("Query.items(first): Int → String"),
+            ]
+        );
+    }
+
+    #[test]
+    fn breaking() {
+        let breaking: Vec<Change> = diff_sdl(OLD, NEW)
+            .into_iter()
+            .filter(|c| c.breaking())
+            .collect();
+        assert_eq!(breaking.len(), 5);
+        assert!(!breaking.iter().any(|c| matches!(c, Change::Added(_))));
+    }
+
+    #[test]
+    fn added_and_removed_types() {
+        let query = "type Query { a: Int }";
+        let other = "type Query { a: Int }\nenum Other { A }";
+        assert_eq!(
+            diff_sdl(other, query),
+            vec![Change::Removed("enum Other".to_string())]
+        );
+        assert_eq!(
+            diff_sdl(query, other),
+            vec![Change::Added("enum Other".to_string())]
+        );
+    }
+}
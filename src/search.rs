@@ -0,0 +1,247 @@
+//! Full-text search over work item titles, descriptions and comments for `km search`
+
+use anyhow::Result;
+
+use colored::*;
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+use crate::api::{
+    search_items_query, search_items_with_comments_query, Api, Date, SearchItemsQuery,
+    SearchItemsWithCommentsQuery,
+};
+use crate::cache;
+
+/// Characters of context shown before and after the first match in a snippet
+const SNIPPET_BEFORE: usize = 30;
+const SNIPPET_AFTER: usize = 70;
+
+/// The searchable parts of a work item
+#[derive(Serialize, Deserialize)]
+pub struct Document {
+    pub number: String,
+    pub title: String,
+    pub description: String,
+    pub status: String,
+    pub archived: bool,
+    pub updated_at: Date,
+    /// `None` when comments weren't fetched
+    pub comments: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedSpace {
+    with_comments: bool,
+    documents: Vec<Document>,
+}
+
+/// Fetch every work item in a space, or use the cached copy if it is recent enough
+pub async fn documents(
+    api: &Api,
+    space_id: &str,
+    space_key: &str,
+    with_comments: bool,
+    max_age: Option<Duration>,
+) -> Result<Vec<Document>> {
+    let cache_name = format!("search-{:}", space_key);
+    if let Some(cached) = cache::load::<CachedSpace>(&cache_name, max_age) {
+        if cached.with_comments || !with_comments {
+            let mut documents = cached.documents;
+            // Comments cached for an earlier --comments search shouldn't match this one
+            if !with_comments {
+                documents.iter_mut().for_each(|d| d.comments = None);
+            }
+            return Ok(documents);
+        }
+    }
+
+    let mut documents = Vec::new();
+    let mut has_more = true;
+    let mut cursor: Option<String> = None;
+
+    while has_more {
+        if with_comments {
+            let response_data = api
+                .query::<SearchItemsWithCommentsQuery>(
+                    search_items_with_comments_query::Variables {
+                        space_id: space_id.to_string(),
+                        cursor,
+                    },
+                )
+                .await?;
+            has_more = response_data.work_items.has_more;
+            cursor = Some(response_data.work_items.cursor);
+            documents.extend(
+                response_data
+                    .work_items
+                    .work_items
+                    .into_iter()
+                    .map(|i| Document {
+                        number: i.number,
+                        title: i.title,
+                        description: i.description,
+                        status: i.status.name,
+                        archived: i.status.type_
+                            == search_items_with_comments_query::StatusType::ARCHIVED,
+                        updated_at: i.updated_at,
+                        comments: Some(i.comments.into_iter().map(|c| c.body).collect()),
+                    }),
+            );
+        } else {
+            let response_data = api
+                .query::<SearchItemsQuery>(search_items_query::Variables {
+                    space_id: space_id.to_string(),
+                    cursor,
+                })
+                .await?;
+            has_more = response_data.work_items.has_more;
+            cursor = Some(response_data.work_items.cursor);
+            documents.extend(
+                response_data
+                    .work_items
+                    .work_items
+                    .into_iter()
+                    .map(|i| Document {
+                        number: i.number,
+                        title: i.title,
+                        description: i.description,
+                        status: i.status.name,
+                        archived: i.status.type_ == search_items_query::StatusType::ARCHIVED,
+                        updated_at: i.updated_at,
+                        comments: None,
+                    }),
+            );
+        }
+    }
+
+    let cached = CachedSpace {
+        with_comments,
+        documents,
+    };
+    // A cache we can't write is not worth failing the search over
+    let _ = cache::store(&cache_name, &cached);
+    Ok(cached.documents)
+}
+
+/// Numbers of the work items in a space's search cache, however old
+pub fn cached_numbers(space_key: &str) -> Vec<String> {
+    cache::load::<CachedSpace>(&format!("search-{:}", space_key), None)
+        .map(|cached| cached.documents.into_iter().map(|d| d.number).collect())
+        .unwrap_or_default()
+}
+
+/// Split a query into lowercase terms, keeping "quoted phrases" together
+pub fn terms(query: &str) -> Vec<String> {
+    query
+        .split('"')
+        .enumerate()
+        .flat_map(|(i, part)| {
+            if i % 2 == 1 {
+                vec![part.trim().to_lowercase()]
+            } else {
+                part.split_whitespace().map(|w| w.to_lowercase()).collect()
+            }
+        })
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn lower_chars(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Character ranges where any of the terms occur, in order and without overlaps
+fn matches(text: &str, terms: &[String]) -> Vec<(usize, usize)> {
+    let haystack = lower_chars(text);
+    let mut found: Vec<(usize, usize)> = Vec::new();
+    for term in terms {
+        let needle: Vec<char> = term.chars().collect();
+        if needle.is_empty() || needle.len() > haystack.len() {
+            continue;
+        }
+        for start in 0..=haystack.len() - needle.len() {
+            if haystack[start..start + needle.len()] == needle[..] {
+                found.push((start, start + needle.len()));
+            }
+        }
+    }
+    found.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in found {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn count(text: &str, term: &str) -> usize {
+    text.to_lowercase().matches(term).count()
+}
+
+/// Relevance of a document, or `None` unless every term occurs somewhere in it.
+/// Title matches weigh the most, then the description, then comments.
+pub fn score(doc: &Document, terms: &[String]) -> Option<usize> {
+    let mut score = 0;
+    for term in terms {
+        let title = count(&doc.title, term);
+        let description = count(&doc.description, term);
+        let comments: usize = doc.comments.iter().flatten().map(|c| count(c, term)).sum();
+        if title + description + comments == 0 {
+            return None;
+        }
+        score += 10 * title.min(3) + 3 * description.min(5) + comments.min(5);
+    }
+
+    // Reward the terms appearing together
+    let phrase = terms.join(" ");
+    if terms.len() > 1 {
+        if doc.title.to_lowercase().contains(&phrase) {
+            score += 20;
+        } else if doc.description.to_lowercase().contains(&phrase) {
+            score += 5;
+        }
+    }
+    Some(score)
+}
+
+/// The text with every match of the terms highlighted
+pub fn highlight(text: &str, terms: &[String]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut pos = 0;
+    for (start, end) in matches(text, terms) {
+        out.extend(&chars[pos..start]);
+        let matched: String = chars[start..end].iter().collect();
+        out.push_str(&matched.bold().yellow().to_string());
+        pos = end;
+    }
+    out.extend(&chars[pos..]);
+    out
+}
+
+/// A single line of context around the first match in the description or comments
+pub fn snippet(doc: &Document, terms: &[String]) -> Option<String> {
+    let text = std::iter::once(&doc.description)
+        .chain(doc.comments.iter().flatten())
+        .map(|t| t.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .find(|t| !matches(t, terms).is_empty())?;
+
+    let chars: Vec<char> = text.chars().collect();
+    let (start, _) = matches(&text, terms)[0];
+    let from = start.saturating_sub(SNIPPET_BEFORE);
+    let to = (start + SNIPPET_AFTER).min(chars.len());
+    let excerpt: String = chars[from..to].iter().collect();
+
+    Some(format!(
+        "{:}{:}{:}",
+        if from > 0 { "…" } else { "" },
+        highlight(&excerpt, terms),
+        if to < chars.len() { "…" } else { "" }
+    ))
+}
@@ -0,0 +1,172 @@
+//! Work item templates for `km item create --template NAME`
+//!
+//! A template is a markdown file `NAME.md` in a `.km/templates/` directory (looked up from
+//! the current directory upwards) or in `templates/` next to the config file. It may start
+//! with TOML front matter between `+++` lines giving defaults for the new work item:
+//!
+//! ```text
+//! +++
+//! title = "Bug: {{summary}}"
+//! status = "Triage"
+//! labels = ["bug"]
+//! members = ["me"]
+//! effort = "S"
+//! +++
+//! ## Steps to reproduce
+//!
+//! {{steps}}
+//! ```
+//!
+//! Every `{{placeholder}}` in the title and body is asked for when the template is used.
+
+use anyhow::{bail, Context, Result};
+
+use regex::{Captures, Regex};
+
+use serde::Deserialize;
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::picker;
+
+const FENCE: &str = "+++";
+
+/// Defaults from a template's front matter
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub status: Option<String>,
+    pub labels: Vec<String>,
+    pub members: Vec<String>,
+    pub watchers: Vec<String>,
+    pub effort: Option<String>,
+    pub impact: Option<String>,
+}
+
+pub struct Template {
+    pub front: FrontMatter,
+    pub body: String,
+}
+
+/// Where templates are looked for, most specific first
+fn dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Ok(cwd) = std::env::current_dir() {
+        for dir in cwd.ancestors() {
+            let templates = dir.join(".km").join("templates");
+            if templates.is_dir() {
+                dirs.push(templates);
+                break;
+            }
+        }
+    }
+    if let Some(config_dir) = Config::path().parent() {
+        dirs.push(config_dir.join("templates"));
+    }
+    dirs
+}
+
+/// Names of all available templates
+fn names() -> Vec<String> {
+    let mut names: Vec<String> = dirs()
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            match path.extension() {
+                Some(ext) if ext == "md" => Some(path.file_stem()?.to_string_lossy().to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+pub fn load(name: &str) -> Result<Template> {
+    let path = match dirs()
+        .into_iter()
+        .map(|dir| dir.join(format!("{:}.md", name)))
+        .find(|path| path.is_file())
+    {
+        Some(path) => path,
+        None => {
+            let names = names();
+            if names.is_empty() {
+                bail!(
+                    "Could not find template {:} (no templates in .km/templates or {:})",
+                    name,
+                    dirs()
+                        .last()
+                        .map(|d| d.display().to_string())
+                        .unwrap_or_default()
+                );
+            }
+            bail!(
+                "Could not find template {:} (one of: {:})",
+                name,
+                names.join(", ")
+            );
+        }
+    };
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Could not read template {:}", path.display()))?;
+    parse(&text).with_context(|| format!("Invalid template {:}", path.display()))
+}
+
+fn parse(text: &str) -> Result<Template> {
+    let rest = match text.strip_prefix(FENCE) {
+        Some(rest) if rest.starts_with(['\n', '\r']) => rest,
+        _ => {
+            return Ok(Template {
+                front: FrontMatter::default(),
+                body: text.to_string(),
+            })
+        }
+    };
+    let end = match rest.find(&format!("\n{:}", FENCE)) {
+        Some(end) => end,
+        None => bail!("Front matter is missing its closing {:}", FENCE),
+    };
+    let front = toml::from_str(&rest[..end])?;
+    let body = rest[end + 1 + FENCE.len()..]
+        .trim_start_matches(['\r', '\n'])
+        .to_string();
+    Ok(Template { front, body })
+}
+
+fn placeholder() -> Regex {
+    Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}").unwrap()
+}
+
+impl Template {
+    /// Ask for every placeholder in the title and body and fill them in
+    pub fn fill(&mut self) -> Result<()> {
+        let re = placeholder();
+        let texts = self.front.title.iter().chain(std::iter::once(&self.body));
+        let mut values: BTreeMap<String, String> = BTreeMap::new();
+        for text in texts {
+            for caps in re.captures_iter(text) {
+                if let Entry::Vacant(entry) = values.entry(caps[1].to_string()) {
+                    let value = picker::prompt(entry.key())?.unwrap_or_default();
+                    entry.insert(value);
+                }
+            }
+        }
+
+        let replace = |text: &str| {
+            re.replace_all(text, |caps: &Captures| values[&caps[1]].clone())
+                .to_string()
+        };
+        self.front.title = self.front.title.as_deref().map(replace);
+        self.body = replace(&self.body);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! Logging of API traffic on standard error (`-v`, `-vv` or `$KM_LOG`), and of whole
+//! requests and responses to a JSONL file (`--trace-file`) for bug reports
+//!
+//! The token is replaced with `[redacted]` wherever it shows up.
+
+use anyhow::{Context, Result};
+
+use colored::*;
+
+use reqwest::StatusCode;
+
+use serde_json::{json, Value};
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    /// Operation names, statuses, timing and response sizes
+    Info,
+    /// Also variables and page cursors
+    Debug,
+}
+
+impl Level {
+    /// The level for `-v` given `count` times
+    pub fn from_count(count: u64) -> Level {
+        match count {
+            0 => Level::Off,
+            1 => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "off" | "0" | "" => Ok(Level::Off),
+            "info" | "1" => Ok(Level::Info),
+            "debug" | "2" => Ok(Level::Debug),
+            _ => Err(format!("Unknown log level {:} (off, info or debug)", s)),
+        }
+    }
+}
+
+/// How a request ended
+pub enum Outcome<'a> {
+    Response { status: StatusCode, body: &'a str },
+    Failed(&'a reqwest::Error),
+}
+
+pub struct Tracer {
+    level: Level,
+    file: Option<Mutex<File>>,
+    token: String,
+}
+
+impl Tracer {
+    /// Log at `level`, appending to `trace_file` if given
+    pub fn new(level: Level, trace_file: Option<&Path>, token: &str) -> Result<Tracer> {
+        let file = match trace_file {
+            None => None,
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Could not open trace file {:}", path.display()))?,
+            )),
+        };
+        Ok(Tracer {
+            level,
+            file,
+            token: token.to_string(),
+        })
+    }
+
+    fn redact(&self, text: &str) -> String {
+        if self.token.is_empty() {
+            text.to_string()
+        } else {
+            text.replace(&self.token, "[redacted]")
+        }
+    }
+
+    fn log(&self, level: Level, message: impl FnOnce() -> String) {
+        if self.level >= level {
+            eprintln!("{:} {:}", "km:".dimmed(), self.redact(&message()));
+        }
+    }
+
+    /// Log a request about to be sent
+    pub fn request(&self, name: &str, body: &Value) {
+        self.log(Level::Debug, || {
+            format!(
+                "{:} variables {:}",
+                name.bold(),
+                body.get("variables").unwrap_or(&Value::Null)
+            )
+        });
+    }
+
+    /// Log how a request went and write it to the trace file
+    pub fn exchange(
+        &self,
+        name: &str,
+        url: &str,
+        body: &Value,
+        elapsed: Duration,
+        outcome: Outcome,
+    ) {
+        let millis = elapsed.as_millis();
+        match &outcome {
+            Outcome::Response { status, body } => {
+                self.log(Level::Info, || {
+                    format!(
+                        "{:} {:} in {:} ms, {:}",
+                        name.bold(),
+                        status,
+                        millis,
+                        size(body.len())
+                    )
+                });
+                if self.level >= Level::Debug {
+                    for (field, cursor, more) in cursors(body) {
+                        self.log(Level::Debug, || {
+                            format!(
+                                "{:} {:} cursor {:}{:}",
+                                name.bold(),
+                                field,
+                                cursor,
+                                if more { ", more to come" } else { "" }
+                            )
+                        });
+                    }
+                }
+            }
+            Outcome::Failed(e) => {
+                self.log(Level::Info, || {
+                    format!("{:} failed after {:} ms: {:}", name.bold(), millis, e)
+                });
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let response = match outcome {
+                Outcome::Response { status, body } => json!({
+                    "status": status.as_u16(),
+                    "body": serde_json::from_str::<Value>(body)
+                        .unwrap_or_else(|_| Value::String(body.to_string())),
+                }),
+                Outcome::Failed(e) => json!({ "error": e.to_string() }),
+            };
+            let line = json!({
+                "time": chrono::Utc::now().to_rfc3339(),
+                "operation": name,
+                "milliseconds": millis as u64,
+                "request": {
+                    "url": url,
+                    "headers": { "authorization": "Bearer [redacted]" },
+                    "body": body,
+                },
+                "response": response,
+            });
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{:}", self.redact(&line.to_string()));
+            }
+        }
+    }
+}
+
+fn size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{:} B", bytes)
+    } else {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    }
+}
+
+/// `cursor` and `hasMore` of the paged results in a response
+fn cursors(body: &str) -> Vec<(String, String, bool)> {
+    let response: Value = match serde_json::from_str(body) {
+        Ok(response) => response,
+        Err(_) => return Vec::new(),
+    };
+    response["data"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(field, page)| {
+            let cursor = page.get("cursor")?.as_str()?;
+            let more = page.get("hasMore").and_then(Value::as_bool);
+            Some((field.clone(), cursor.to_string(), more.unwrap_or(false)))
+        })
+        .collect()
+}